@@ -1,65 +1,293 @@
+pub mod index;
+pub mod script;
+mod ttyrec;
 mod util;
-mod v1;
+pub mod v1;
 mod v2;
-use anyhow::{anyhow, Result};
+mod v3;
+mod validate;
+use crate::compress;
+use anyhow::{anyhow, bail, Result};
+pub use index::Index;
+use rgb::RGB8;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
 use std::collections::HashMap;
+use std::fmt;
 use std::fs;
-use std::io::{self, BufRead};
+use std::io::{self, BufRead, Read, Seek};
 use std::path::Path;
+use std::rc::Rc;
+pub use v1::Writer as V1Writer;
 pub use v2::Writer;
+pub use v3::Writer as V3Writer;
+pub use validate::{validate, ValidationReport};
 
+/// A recording: its header and a lazily parsed stream of events.
 pub struct Asciicast<'a> {
     pub header: Header,
     pub events: Box<dyn Iterator<Item = Result<Event>> + 'a>,
+    pub position: Position,
 }
 
+/// Location of the line the most recently parsed event was read from, kept
+/// up to date by line based parsers (asciicast v2 and v3).
+#[derive(Clone, Debug, Default)]
+pub struct Position(Rc<Cell<Option<(usize, u64)>>>);
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[non_exhaustive]
 pub struct Header {
+    /// asciicast version, or 0 for recordings imported from other formats.
     pub version: u8,
     pub cols: u16,
     pub rows: u16,
+    pub term_type: Option<String>,
+    pub term_version: Option<String>,
     pub timestamp: Option<u64>,
     pub idle_time_limit: Option<f64>,
     pub command: Option<String>,
     pub title: Option<String>,
     pub env: Option<HashMap<String, String>>,
+    pub theme: Option<Theme>,
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "util::ThemeJson", into = "util::ThemeJson")]
+#[non_exhaustive]
+pub struct Theme {
+    pub fg: RGB8,
+    pub bg: RGB8,
+    pub palette: Vec<RGB8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Version {
+    V1,
+    V2,
+    V3,
 }
 
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[non_exhaustive]
 pub struct Event {
+    /// Time since the start of the recording, in microseconds.
     pub time: u64,
     pub data: EventData,
 }
 
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+#[non_exhaustive]
 pub enum EventData {
     Output(String),
     Input(String),
+    Bytes(Vec<u8>),
     Resize(u16, u16),
     Marker(String),
-    Other(char, String),
+    Exit(i32),
+    Other(String, String),
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: Option<usize>,
+    pub offset: u64,
+    pub message: String,
 }
 
 pub fn open_from_path<S: AsRef<Path>>(path: S) -> Result<Asciicast<'static>> {
     fs::File::open(path)
-        .map(io::BufReader::new)
+        .and_then(compress::decoder)
         .map_err(|e| anyhow!(e))
         .and_then(open)
         .map_err(|e| anyhow!("can't open asciicast file: {e}"))
 }
 
-pub fn open<'a, R: BufRead + 'a>(reader: R) -> Result<Asciicast<'a>> {
-    let mut lines = reader.lines();
-    let first_line = lines.next().ok_or(anyhow!("empty file"))??;
+/// Opens a recording, skipping damaged event lines (e.g. a half-written
+/// last line of an interrupted recording) instead of failing on them.
+/// `on_damage` is called for every skipped line.
+pub fn open_from_path_tolerant<S, F>(path: S, on_damage: F) -> Result<Asciicast<'static>>
+where
+    S: AsRef<Path>,
+    F: FnMut(&ParseError) + 'static,
+{
+    let Asciicast {
+        header,
+        events,
+        position,
+    } = open_from_path(path)?;
+
+    let events = Box::new(skip_damaged(events, on_damage));
+
+    Ok(Asciicast {
+        header,
+        events,
+        position,
+    })
+}
+
+/// Opens a recording positioned at `time`, using the recording's index
+/// (see [`Index`]) when it has an up to date one, or scanning it from the
+/// beginning otherwise. Event times are relative to `time`, and the header
+/// has the terminal size in effect at that time.
+pub fn open_from_path_at<S: AsRef<Path>>(path: S, time: u64) -> Result<Asciicast<'static>> {
+    let path = path.as_ref();
+
+    let checkpoint = Index::load(path)
+        .ok()
+        .flatten()
+        .and_then(|index| index.checkpoint(time).cloned());
+
+    let recording = match checkpoint {
+        Some(checkpoint) => open_at_checkpoint(path, &checkpoint)
+            .map_err(|e| anyhow!("can't open asciicast file: {e}"))?,
+
+        None => open_from_path(path)?,
+    };
+
+    Ok(seek(recording, time))
+}
+
+fn open_at_checkpoint(path: &Path, checkpoint: &index::Checkpoint) -> Result<Asciicast<'static>> {
+    let mut reader = io::BufReader::new(fs::File::open(path)?);
+    let mut header_line = String::new();
+    reader.read_line(&mut header_line)?;
+    reader.seek(io::SeekFrom::Start(checkpoint.offset))?;
+    let lines = util::Lines::starting_at(reader, checkpoint.line, checkpoint.offset);
+
+    let mut recording = if let Ok(parser) = v2::open(&header_line) {
+        parser.parse(lines)
+    } else {
+        v3::open(&header_line)?.parse_from(lines, checkpoint.base_time)
+    };
+
+    recording.header.cols = checkpoint.cols;
+    recording.header.rows = checkpoint.rows;
+
+    let screen = Event::output(checkpoint.base_time, checkpoint.screen.as_bytes());
+    recording.events = Box::new(std::iter::once(Ok(screen)).chain(recording.events));
+
+    Ok(recording)
+}
+
+/// Skips events preceding `time`, keeping track of resizes. The screen
+/// drawn by the skipped output is reproduced by a single output event at
+/// the start. Errors are passed through so that they can still be handled
+/// by the consumer. Event times of the returned recording are relative to
+/// `time`.
+pub fn seek(recording: Asciicast<'_>, time: u64) -> Asciicast<'_> {
+    let Asciicast {
+        mut header,
+        mut events,
+        position,
+    } = recording;
+
+    let mut vt = terminal((header.cols, header.rows));
+    let mut skipped_output = false;
+    let mut head = Vec::new();
+
+    for event in events.by_ref() {
+        match event {
+            Ok(event) if event.time >= time => {
+                head.push(Ok(event));
+                break;
+            }
+
+            Ok(event) => {
+                if let EventData::Resize(cols, rows) = event.data {
+                    header.cols = cols;
+                    header.rows = rows;
+                }
+
+                skipped_output |= feed(&mut vt, &event.data);
+            }
+
+            Err(e) => head.push(Err(e)),
+        }
+    }
+
+    if skipped_output {
+        head.insert(0, Ok(Event::output(time, vt.dump().as_bytes())));
+    }
+
+    let events = Box::new(head.into_iter().chain(events).map(move |event| {
+        event.map(|event| Event {
+            time: event.time.saturating_sub(time),
+            ..event
+        })
+    }));
+
+    Asciicast {
+        header,
+        events,
+        position,
+    }
+}
+
+/// Virtual terminal for keeping track of the screen while skipping events.
+fn terminal((cols, rows): (u16, u16)) -> avt::Vt {
+    avt::Vt::builder()
+        .size(cols as usize, rows as usize)
+        .resizable(true)
+        .scrollback_limit(0)
+        .build()
+}
+
+/// Feeds output and resizes to the terminal, returning false for other
+/// events.
+fn feed(vt: &mut avt::Vt, data: &EventData) -> bool {
+    match data {
+        EventData::Output(data) => vt.feed_str(data),
+        EventData::Bytes(data) => vt.feed_str(&String::from_utf8_lossy(data)),
+        EventData::Resize(cols, rows) => vt.feed_str(&format!("\x1b[8;{rows};{cols}t")),
+        _ => return false,
+    };
+
+    true
+}
+
+/// Opens a recording in any of the supported formats: asciicast v1, v2 and
+/// v3, or ttyrec.
+pub fn open<'a, R: BufRead + 'a>(mut reader: R) -> Result<Asciicast<'a>> {
+    let mut head = Vec::with_capacity(ttyrec::HEADER_LEN);
+    (&mut reader)
+        .take(ttyrec::HEADER_LEN as u64)
+        .read_to_end(&mut head)?;
+
+    let sniffed = ttyrec::sniff(&head);
+    let reader = io::Cursor::new(head).chain(reader);
+
+    if sniffed {
+        return ttyrec::open(reader);
+    }
+
+    let mut lines = util::Lines::new(reader);
+    let first_line = lines.next().ok_or(anyhow!("empty file"))?.text?;
 
     if let Ok(parser) = v2::open(&first_line) {
         Ok(parser.parse(lines))
+    } else if let Ok(parser) = v3::open(&first_line) {
+        Ok(parser.parse(lines))
     } else {
         let json = std::iter::once(Ok(first_line))
-            .chain(lines)
-            .collect::<io::Result<String>>()?;
+            .chain(lines.map(|line| line.text))
+            .collect::<io::Result<Vec<String>>>()?
+            .join("\n");
 
         v1::load(json)
     }
 }
 
+pub fn get_version<S: AsRef<Path>>(path: S) -> Result<u8> {
+    let Asciicast { header, .. } = open_from_path(path)?;
+
+    Ok(header.version)
+}
+
 pub fn get_duration<S: AsRef<Path>>(path: S) -> Result<u64> {
     let Asciicast { events, .. } = open_from_path(path)?;
     let time = events.last().map_or(Ok(0), |e| e.map(|e| e.time))?;
@@ -67,7 +295,75 @@ pub fn get_duration<S: AsRef<Path>>(path: S) -> Result<u64> {
     Ok(time)
 }
 
+/// Removes the exit event a recording ends with, if any, so that events
+/// appended to it don't follow the exit. Returns true when there was one.
+pub(crate) fn remove_exit_event<S: AsRef<Path>>(path: S) -> Result<bool> {
+    let path = path.as_ref();
+    let Asciicast {
+        events, position, ..
+    } = open_from_path(path)?;
+
+    let mut last = None;
+
+    for event in events {
+        last = Some((event?, position.offset()));
+    }
+
+    let Some((
+        Event {
+            data: EventData::Exit(_),
+            ..
+        },
+        Some(offset),
+    )) = last
+    else {
+        return Ok(false);
+    };
+
+    if compress::is_compressed(fs::File::open(path)?)? {
+        bail!("can't append to a compressed recording ending with an exit event");
+    }
+
+    fs::OpenOptions::new()
+        .write(true)
+        .open(path)?
+        .set_len(offset)?;
+
+    Ok(true)
+}
+
+impl Header {
+    /// Creates a header of an asciicast `version` recording of a `cols`x`rows`
+    /// terminal, with all optional fields unset.
+    pub fn new(version: u8, cols: u16, rows: u16) -> Self {
+        Header {
+            version,
+            cols,
+            rows,
+            term_type: None,
+            term_version: None,
+            timestamp: None,
+            idle_time_limit: None,
+            command: None,
+            title: None,
+            env: None,
+            theme: None,
+            extra: serde_json::Map::new(),
+        }
+    }
+}
+
+impl Theme {
+    pub fn new(fg: RGB8, bg: RGB8, palette: Vec<RGB8>) -> Self {
+        Theme { fg, bg, palette }
+    }
+}
+
 impl Event {
+    pub fn new(time: u64, data: EventData) -> Self {
+        Event { time, data }
+    }
+
     pub fn output(time: u64, data: &[u8]) -> Self {
         Event {
             time,
@@ -75,6 +371,13 @@ impl Event {
         }
     }
 
+    pub fn bytes(time: u64, data: &[u8]) -> Self {
+        Event {
+            time,
+            data: EventData::Bytes(data.to_vec()),
+        }
+    }
+
     pub fn input(time: u64, data: &[u8]) -> Self {
         Event {
             time,
@@ -95,6 +398,106 @@ impl Event {
             data: EventData::Marker(label),
         }
     }
+
+    pub fn exit(time: u64, status: i32) -> Self {
+        Event {
+            time,
+            data: EventData::Exit(status),
+        }
+    }
+}
+
+pub fn skip_damaged<'a>(
+    events: impl Iterator<Item = Result<Event>> + 'a,
+    mut on_damage: impl FnMut(&ParseError) + 'a,
+) -> impl Iterator<Item = Result<Event>> + 'a {
+    events.filter(move |event| match event {
+        Err(e) => match e.downcast_ref::<ParseError>() {
+            Some(e) => {
+                on_damage(e);
+
+                false
+            }
+
+            None => true,
+        },
+
+        Ok(_) => true,
+    })
+}
+
+/// What to do with events whose time is earlier than the preceding event's.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub enum TimePolicy {
+    /// Move them to the preceding event's time
+    #[default]
+    Clamp,
+    /// Move them to their place in time order
+    Reorder,
+    /// Stop with an error
+    Error,
+}
+
+/// Makes event times non-decreasing according to `policy`. `Reorder` needs
+/// to read all events upfront.
+pub fn normalize_time<'a>(recording: Asciicast<'a>, policy: TimePolicy) -> Asciicast<'a> {
+    let Asciicast {
+        header,
+        events,
+        position,
+    } = recording;
+
+    let mut prev_time = 0;
+
+    let events: Box<dyn Iterator<Item = Result<Event>> + 'a> = match policy {
+        TimePolicy::Clamp => Box::new(events.map(move |event| {
+            event.map(|event| {
+                prev_time = prev_time.max(event.time);
+
+                Event {
+                    time: prev_time,
+                    ..event
+                }
+            })
+        })),
+
+        TimePolicy::Reorder => match events.collect::<Result<Vec<_>>>() {
+            Ok(mut events) => {
+                events.sort_by_key(|event| event.time);
+
+                Box::new(events.into_iter().map(Ok))
+            }
+
+            Err(e) => Box::new(std::iter::once(Err(e))),
+        },
+
+        TimePolicy::Error => {
+            let position = position.clone();
+
+            Box::new(events.map(move |event| {
+                let event = event?;
+
+                if event.time < prev_time {
+                    return Err(position.error(format!(
+                        "event at {}s is earlier than the preceding event at {}s",
+                        util::format_time(event.time),
+                        util::format_time(prev_time)
+                    )));
+                }
+
+                prev_time = event.time;
+
+                Ok(event)
+            }))
+        }
+    };
+
+    Asciicast {
+        header,
+        events,
+        position,
+    }
 }
 
 pub fn limit_idle_time(
@@ -107,14 +510,14 @@ pub fn limit_idle_time(
 
     events.map(move |event| {
         event.map(|event| {
-            let delay = event.time - prev_time;
+            let delay = event.time.saturating_sub(prev_time);
 
             if delay > limit {
                 offset += delay - limit;
             }
 
             prev_time = event.time;
-            let time = event.time - offset;
+            let time = event.time.saturating_sub(offset);
 
             Event { time, ..event }
         })
@@ -134,16 +537,95 @@ pub fn accelerate(
     })
 }
 
+/// Ends the stream right after the nth (1-based) marker, failing when the
+/// recording has fewer markers.
+pub fn until_marker<'a>(
+    mut events: impl Iterator<Item = Result<Event>> + 'a,
+    n: usize,
+) -> impl Iterator<Item = Result<Event>> + 'a {
+    let mut seen = 0;
+    let mut done = false;
+
+    std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+
+        match events.next() {
+            Some(Ok(event)) => {
+                if let EventData::Marker(_) = event.data {
+                    seen += 1;
+                    done = seen == n;
+                }
+
+                Some(Ok(event))
+            }
+
+            Some(Err(e)) => Some(Err(e)),
+
+            None => {
+                done = true;
+
+                Some(Err(anyhow!(
+                    "marker {n} not found, the recording has {seen} marker(s)"
+                )))
+            }
+        }
+    })
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, ", self.line)?;
+
+        if let Some(column) = self.column {
+            write!(f, "column {}, ", column)?;
+        }
+
+        write!(f, "byte offset {}: {}", self.offset, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Position {
+    pub(crate) fn set(&self, line: &util::Line) {
+        self.0.set(Some((line.number, line.offset)));
+    }
+
+    /// Byte offset of the line the most recently parsed event was read from.
+    pub(crate) fn offset(&self) -> Option<u64> {
+        self.0.get().map(|(_, offset)| offset)
+    }
+
+    /// Returns an error about the most recently parsed event, as a
+    /// [`ParseError`] when its location is known.
+    pub fn error<E: fmt::Display>(&self, e: E) -> anyhow::Error {
+        match self.0.get() {
+            Some((line, offset)) => ParseError {
+                line,
+                column: None,
+                offset,
+                message: e.to_string(),
+            }
+            .into(),
+
+            None => anyhow!("{e}"),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{Asciicast, Event, EventData, Header, Writer};
+    use super::{Asciicast, Event, EventData, Header, ParseError, V3Writer, Writer};
     use anyhow::Result;
+    use rgb::RGB8;
     use std::collections::HashMap;
     use std::io;
 
     #[test]
     fn open_v1_minimal() {
-        let Asciicast { header, events } =
+        let Asciicast { header, events, .. } =
             super::open_from_path("tests/casts/minimal.json").unwrap();
 
         let events = events.collect::<Result<Vec<Event>>>().unwrap();
@@ -157,7 +639,8 @@ mod tests {
 
     #[test]
     fn open_v1_full() {
-        let Asciicast { header, events } = super::open_from_path("tests/casts/full.json").unwrap();
+        let Asciicast { header, events, .. } =
+            super::open_from_path("tests/casts/full.json").unwrap();
         let events = events.collect::<Result<Vec<Event>>>().unwrap();
 
         assert_eq!(header.version, 1);
@@ -166,16 +649,26 @@ mod tests {
         assert_eq!(events[0].time, 1);
         assert!(matches!(events[0].data, EventData::Output(ref s) if s == "ż"));
 
-        assert_eq!(events[1].time, 1000000);
+        assert_eq!(events[1].time, 1000001);
         assert!(matches!(events[1].data, EventData::Output(ref s) if s == "ółć"));
 
-        assert_eq!(events[2].time, 10500000);
+        assert_eq!(events[2].time, 11500001);
         assert!(matches!(events[2].data, EventData::Output(ref s) if s == "\r\n"));
     }
 
+    #[test]
+    fn open_v1_delays() {
+        let Asciicast { events, .. } = super::open_from_path("tests/casts/delays.json").unwrap();
+        let events = events.collect::<Result<Vec<Event>>>().unwrap();
+        let times: Vec<u64> = events.iter().map(|e| e.time).collect();
+
+        assert_eq!(times, vec![500000, 500000, 2750000]);
+    }
+
     #[test]
     fn open_v2() {
-        let Asciicast { header, events } = super::open_from_path("tests/casts/demo.cast").unwrap();
+        let Asciicast { header, events, .. } =
+            super::open_from_path("tests/casts/demo.cast").unwrap();
         let events = events.take(7).collect::<Result<Vec<Event>>>().unwrap();
 
         assert_eq!((header.cols, header.rows), (75, 18));
@@ -190,6 +683,100 @@ mod tests {
         assert!(matches!(events[6].data, EventData::Output(ref s) if s == "v"));
     }
 
+    #[test]
+    fn open_v2_damaged() {
+        let data = "{\"version\": 2, \"width\": 80, \"height\": 24}\n[1.0, \"o\", \"foo\"]\n[2.0, \"o\", \"ba";
+        let Asciicast { events, .. } = super::open(io::Cursor::new(data)).unwrap();
+        let events = events.collect::<Vec<_>>();
+
+        assert_eq!(events.len(), 2);
+        assert!(events[0].is_ok());
+
+        let e = events[1].as_ref().err().unwrap();
+        let e = e.downcast_ref::<ParseError>().unwrap();
+
+        assert_eq!(e.line, 3);
+        assert_eq!(e.column, Some(14));
+        assert_eq!(e.offset, 60);
+    }
+
+    #[test]
+    fn bytes_events() {
+        let data = "{\"version\": 2, \"width\": 80, \"height\": 24}\n[1.0, \"asciinema.b\", \"/wA=\"]\n[2.0, \"b\", \"foo\"]\n[3.0, \"asciinema.b\", \"foo!\"]\n";
+        let Asciicast { events, .. } = super::open(io::Cursor::new(data)).unwrap();
+        let events = events.collect::<Vec<_>>();
+
+        assert_eq!(
+            events[0].as_ref().unwrap().data,
+            EventData::Bytes(vec![0xff, 0])
+        );
+
+        // other tools' "b" events are kept as is
+        assert_eq!(
+            events[1].as_ref().unwrap().data,
+            EventData::Other("b".to_owned(), "foo".to_owned())
+        );
+
+        let e = events[2].as_ref().unwrap_err().to_string();
+
+        assert!(e.contains("invalid base64 data in asciinema.b event"));
+    }
+
+    #[test]
+    fn event_serde() {
+        let events = vec![
+            Event::output(1_500_000, b"hello"),
+            Event::resize(2_000_000, (100, 40)),
+        ];
+
+        let json = serde_json::to_string(&events).unwrap();
+
+        assert_eq!(
+            json,
+            r#"[{"time":1500000,"data":{"type":"output","data":"hello"}},{"time":2000000,"data":{"type":"resize","data":[100,40]}}]"#
+        );
+
+        assert_eq!(serde_json::from_str::<Vec<Event>>(&json).unwrap(), events);
+    }
+
+    #[test]
+    fn skip_damaged() {
+        let data = "{\"version\": 2, \"width\": 80, \"height\": 24}\n[1.0, \"o\", \"foo\"]\n[2.0, \"o\", \"ba\n[3.0, \"o\", \"baz\"]\n";
+        let Asciicast { events, .. } = super::open(io::Cursor::new(data)).unwrap();
+        let mut damaged = Vec::new();
+
+        let events = super::skip_damaged(events, |e| damaged.push(e.line))
+            .collect::<Result<Vec<Event>>>()
+            .unwrap();
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].time, 3000000);
+        assert_eq!(damaged, vec![3]);
+    }
+
+    #[test]
+    fn skip_damaged_io_error() {
+        struct Failing;
+
+        impl io::Read for Failing {
+            fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk on fire"))
+            }
+        }
+
+        let data = "{\"version\": 2, \"width\": 80, \"height\": 24}\n[1.0, \"o\", \"foo\"]\n";
+        let reader = io::BufReader::new(io::Read::chain(io::Cursor::new(data), Failing));
+        let Asciicast { events, .. } = super::open(reader).unwrap();
+        let mut damaged = 0;
+
+        let events = super::skip_damaged(events, |_| damaged += 1).collect::<Vec<_>>();
+
+        assert_eq!(events.len(), 2);
+        assert!(events[0].is_ok());
+        assert_eq!(events[1].as_ref().unwrap_err().to_string(), "disk on fire");
+        assert_eq!(damaged, 0);
+    }
+
     #[test]
     fn writer() {
         let mut data = Vec::new();
@@ -201,11 +788,15 @@ mod tests {
                 version: 2,
                 cols: 80,
                 rows: 24,
+                term_type: None,
+                term_version: None,
                 timestamp: None,
                 idle_time_limit: None,
                 command: None,
                 title: None,
                 env: Default::default(),
+                theme: None,
+                extra: Default::default(),
             };
 
             fw.write_header(&header).unwrap();
@@ -266,11 +857,15 @@ mod tests {
                 version: 2,
                 cols: 80,
                 rows: 24,
+                term_type: None,
+                term_version: None,
                 timestamp: Some(1704719152),
                 idle_time_limit: Some(1.5),
                 command: Some("/bin/bash".to_owned()),
                 title: Some("Demo".to_owned()),
                 env: Some(env),
+                theme: None,
+                extra: Default::default(),
             };
 
             fw.write_header(&header).unwrap();
@@ -290,6 +885,229 @@ mod tests {
         assert_eq!(lines[0]["env"]["TERM"], "xterm256-color");
     }
 
+    #[test]
+    fn writer_skips_exit() {
+        let mut data = Vec::new();
+
+        {
+            let mut fw = Writer::new(&mut data, 0);
+
+            fw.write_event(&Event::output(1000000, b"foo")).unwrap();
+            fw.write_event(&Event::exit(2000000, 0)).unwrap();
+        }
+
+        let lines = parse(data);
+
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0][1], "o");
+    }
+
+    #[test]
+    fn v2_to_v3_round_trip() {
+        let Asciicast { header, events, .. } =
+            super::open_from_path("tests/casts/demo.cast").unwrap();
+        let events = events.collect::<Result<Vec<Event>>>().unwrap();
+        let mut data = Vec::new();
+
+        {
+            let mut fw = V3Writer::new(&mut data, 0);
+            fw.write_header(&header).unwrap();
+
+            for event in &events {
+                fw.write_event(event).unwrap();
+            }
+        }
+
+        let Asciicast {
+            header: header_v3,
+            events: events_v3,
+            ..
+        } = super::open(io::Cursor::new(data)).unwrap();
+
+        let events_v3 = events_v3.collect::<Result<Vec<Event>>>().unwrap();
+
+        assert_eq!(header_v3.version, 3);
+        assert_eq!((header_v3.cols, header_v3.rows), (75, 18));
+        assert_eq!(header_v3.term_type.as_deref(), Some("xterm-256color"));
+        assert_eq!(header_v3.idle_time_limit, Some(2.0));
+        assert_eq!(events_v3.len(), events.len());
+
+        for (a, b) in events.iter().zip(events_v3.iter()) {
+            assert_eq!(a.time, b.time);
+        }
+    }
+
+    #[test]
+    fn theme() {
+        let palette = "#000000:#dd3c69:#4ebf22:#ddaf3c:#26b0d7:#b954e1:#54e1b9:#d9d9d9";
+        let header = format!(
+            "{{\"version\": 2, \"width\": 80, \"height\": 24, \"theme\": {{\"fg\": \"#D9D9D9\", \"bg\": \"#121314\", \"palette\": \"{palette}\"}}}}"
+        );
+
+        let Asciicast { header, .. } = super::open(io::Cursor::new(header)).unwrap();
+        let theme = header.theme.as_ref().unwrap();
+
+        assert_eq!(theme.fg, RGB8::new(0xd9, 0xd9, 0xd9));
+        assert_eq!(theme.bg, RGB8::new(0x12, 0x13, 0x14));
+        assert_eq!(theme.palette.len(), 8);
+        assert_eq!(theme.palette[1], RGB8::new(0xdd, 0x3c, 0x69));
+
+        let mut data = Vec::new();
+        Writer::new(&mut data, 0).write_header(&header).unwrap();
+        V3Writer::new(&mut data, 0).write_header(&header).unwrap();
+        let lines = parse(data);
+
+        assert_eq!(lines[0]["theme"]["fg"], "#d9d9d9");
+        assert_eq!(lines[0]["theme"]["bg"], "#121314");
+        assert_eq!(lines[0]["theme"]["palette"], palette);
+        assert_eq!(lines[1]["term"]["theme"], lines[0]["theme"]);
+    }
+
+    #[test]
+    fn invalid_theme() {
+        let theme = "{\"fg\": \"#d9d9d9\", \"bg\": \"#121314\", \"palette\": \"#000000:#111111\"}";
+
+        for header in [
+            format!("{{\"version\": 2, \"width\": 80, \"height\": 24, \"theme\": {theme}}}"),
+            format!(
+                "{{\"version\": 3, \"term\": {{\"cols\": 80, \"rows\": 24, \"theme\": {theme}}}}}"
+            ),
+            "{\"version\": 2, \"width\": 80, \"height\": 24, \"theme\": \"dark\"}".to_owned(),
+        ] {
+            let Asciicast { header, .. } = super::open(io::Cursor::new(header)).unwrap();
+
+            assert_eq!((header.cols, header.rows), (80, 24));
+            assert!(header.theme.is_none());
+        }
+    }
+
+    #[test]
+    fn extra_header_fields() {
+        let header = "{\"version\": 2, \"width\": 80, \"height\": 24, \"git_sha\": \"abc123\", \"job\": {\"id\": 42, \"attempt\": 1}}";
+        let Asciicast { mut header, .. } = super::open(io::Cursor::new(header)).unwrap();
+
+        assert_eq!(header.extra.len(), 2);
+        assert_eq!(header.extra["git_sha"], "abc123");
+        assert_eq!(header.extra["job"]["id"], 42);
+
+        // fields named like standard ones are dropped
+        header.extra.insert("width".to_owned(), 1.into());
+        header.extra.insert("term".to_owned(), 1.into());
+
+        let mut data = Vec::new();
+        Writer::new(&mut data, 0).write_header(&header).unwrap();
+        V3Writer::new(&mut data, 0).write_header(&header).unwrap();
+        let lines = String::from_utf8(data).unwrap();
+        let lines: Vec<&str> = lines.lines().collect();
+
+        assert_eq!(
+            lines[0],
+            r#"{"version":2,"width":80,"height":24,"git_sha":"abc123","job":{"id":42,"attempt":1},"term":1}"#
+        );
+
+        assert_eq!(
+            lines[1],
+            r#"{"version":3,"term":{"cols":80,"rows":24},"git_sha":"abc123","job":{"id":42,"attempt":1},"width":1}"#
+        );
+    }
+
+    #[test]
+    fn v2_round_trip_is_stable() {
+        let mut header = round_trip_header();
+        header.version = 2;
+        let original = write_v2(&header, &round_trip_events());
+        let Asciicast { header, events, .. } = super::open(io::Cursor::new(&original)).unwrap();
+        let events = events.collect::<Result<Vec<Event>>>().unwrap();
+
+        assert_eq!(header.version, 2);
+        assert_eq!(write_v2(&header, &events), original);
+    }
+
+    #[test]
+    fn v3_round_trip_is_stable() {
+        let mut events = round_trip_events();
+        events.push(Event::exit(9_000_000, 130));
+        let original = write_v3(&round_trip_header(), &events);
+        let Asciicast { header, events, .. } = super::open(io::Cursor::new(&original)).unwrap();
+        let events = events.collect::<Result<Vec<Event>>>().unwrap();
+
+        assert_eq!(header.version, 3);
+        assert_eq!(write_v3(&header, &events), original);
+    }
+
+    fn round_trip_header() -> Header {
+        let env = HashMap::from([
+            ("TERM".to_owned(), "xterm-256color".to_owned()),
+            ("SHELL".to_owned(), "/bin/zsh".to_owned()),
+            ("LANG".to_owned(), "en_US.UTF-8".to_owned()),
+        ]);
+
+        let extra = serde_json::from_str(r#"{"git_sha": "abc123", "job_id": 1234, "ci": {"runner": "linux", "tags": ["a", "b"]}}"#).unwrap();
+
+        Header {
+            version: 3,
+            cols: 80,
+            rows: 24,
+            term_type: Some("xterm-256color".to_owned()),
+            term_version: Some("VTE(7600)".to_owned()),
+            timestamp: Some(1704719152),
+            idle_time_limit: Some(2.5),
+            command: Some("/bin/zsh -l".to_owned()),
+            title: Some("Round \"trip\"".to_owned()),
+            env: Some(env),
+            theme: Some(super::Theme {
+                fg: RGB8::new(0xd9, 0xd9, 0xd9),
+                bg: RGB8::new(0x12, 0x13, 0x14),
+                palette: vec![RGB8::new(0x20, 0x40, 0x60); 16],
+            }),
+            extra,
+        }
+    }
+
+    fn round_trip_events() -> Vec<Event> {
+        vec![
+            Event::output(0, b"\x1b[1mhello\x1b[0m\r\n"),
+            Event::input(1_000_000, b"\x03"),
+            Event::output(1_000_000, "żółć 🦀\r\n".as_bytes()),
+            Event::resize(2_500_001, (100, 40)),
+            Event::marker(3_000_000, "chapter \"1\"".to_owned()),
+            Event {
+                time: 3_123_456,
+                data: EventData::Other("custom".to_owned(), "{\"a\": 1}".to_owned()),
+            },
+            Event::bytes(8_000_000, b"\xff\x00\xfe"),
+            Event::output(8_999_999, b""),
+        ]
+    }
+
+    fn write_v2(header: &Header, events: &[Event]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut fw = Writer::new(&mut data, 0);
+        fw.write_header(header).unwrap();
+
+        for event in events {
+            fw.write_event(event).unwrap();
+        }
+
+        drop(fw);
+
+        data
+    }
+
+    fn write_v3(header: &Header, events: &[Event]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut fw = V3Writer::new(&mut data, 0);
+        fw.write_header(header).unwrap();
+
+        for event in events {
+            fw.write_event(event).unwrap();
+        }
+
+        drop(fw);
+
+        data
+    }
+
     fn parse(json: Vec<u8>) -> Vec<serde_json::Value> {
         String::from_utf8(json)
             .unwrap()
@@ -332,6 +1150,140 @@ mod tests {
         assert_eq!(events[4], (5_500_000, "quux".to_owned()));
     }
 
+    #[test]
+    fn normalize_time() {
+        use super::TimePolicy;
+
+        let data = concat!(
+            "{\"version\":2,\"width\":80,\"height\":24}\n",
+            "[1.0,\"o\",\"a\"]\n",
+            "[0.5,\"o\",\"b\"]\n",
+            "[2.0,\"o\",\"c\"]\n"
+        );
+
+        let normalize = |policy| {
+            let recording = super::open(io::Cursor::new(data)).unwrap();
+
+            super::normalize_time(recording, policy).events
+        };
+
+        let clamped = output(normalize(TimePolicy::Clamp));
+
+        assert_eq!(
+            clamped,
+            vec![
+                (1_000_000, "a".to_owned()),
+                (1_000_000, "b".to_owned()),
+                (2_000_000, "c".to_owned())
+            ]
+        );
+
+        let reordered = output(normalize(TimePolicy::Reorder));
+
+        assert_eq!(
+            reordered,
+            vec![
+                (500_000, "b".to_owned()),
+                (1_000_000, "a".to_owned()),
+                (2_000_000, "c".to_owned())
+            ]
+        );
+
+        let error = normalize(TimePolicy::Error)
+            .collect::<Result<Vec<_>>>()
+            .unwrap_err();
+
+        let error = error.downcast_ref::<ParseError>().unwrap();
+
+        assert_eq!((error.line, error.offset), (3, 51));
+        assert!(error.message.starts_with("event at 0.5s is earlier"));
+    }
+
+    #[test]
+    fn limit_idle_time_going_back() {
+        let events = [(5_000_000, "foo"), (1_000_000, "bar"), (2_000_000, "baz")]
+            .map(|(time, output)| Ok(Event::output(time, output.as_bytes())));
+
+        let events = output(super::limit_idle_time(events.into_iter(), 2.0));
+
+        assert_eq!(events[0], (2_000_000, "foo".to_owned()));
+        assert_eq!(events[1], (0, "bar".to_owned()));
+        assert_eq!(events[2], (0, "baz".to_owned()));
+    }
+
+    #[test]
+    fn until_marker() {
+        let events = || {
+            [
+                Event::output(0, b"foo"),
+                Event::marker(1, "one".to_owned()),
+                Event::output(2, b"bar"),
+                Event::marker(3, "two".to_owned()),
+                Event::output(4, b"baz"),
+            ]
+            .map(Ok)
+            .into_iter()
+        };
+
+        let events_1 = output(super::until_marker(events(), 1));
+        assert_eq!(events_1, vec![(0, "foo".to_owned())]);
+
+        let events_2 = super::until_marker(events(), 2)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(events_2.len(), 4);
+        assert!(matches!(events_2[3].data, EventData::Marker(ref l) if l == "two"));
+
+        let result = super::until_marker(events(), 3).collect::<Result<Vec<_>>>();
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "marker 3 not found, the recording has 2 marker(s)"
+        );
+    }
+
+    #[test]
+    fn append_after_exit() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+
+        {
+            let mut fw = V3Writer::new(file.reopen().unwrap(), 0);
+            fw.write_header(&Header::new(3, 80, 24)).unwrap();
+            fw.write_event(&Event::output(1_000_000, b"foo")).unwrap();
+            fw.write_event(&Event::exit(2_000_000, 0)).unwrap();
+        }
+
+        assert!(super::remove_exit_event(file.path()).unwrap());
+        assert!(!super::remove_exit_event(file.path()).unwrap());
+
+        let time_offset = super::get_duration(file.path()).unwrap();
+        assert_eq!(time_offset, 1_000_000);
+
+        {
+            let append = std::fs::OpenOptions::new()
+                .append(true)
+                .open(file.path())
+                .unwrap();
+
+            let mut fw = V3Writer::new(append, time_offset);
+            fw.write_event(&Event::output(500_000, b"bar")).unwrap();
+            fw.write_event(&Event::exit(600_000, 0)).unwrap();
+        }
+
+        let Asciicast { events, .. } = super::open_from_path(file.path()).unwrap();
+        let events = events.collect::<Result<Vec<Event>>>().unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                Event::output(1_000_000, b"foo"),
+                Event::output(1_500_000, b"bar"),
+                Event::exit(1_600_000, 0),
+            ]
+        );
+    }
+
     fn output(events: impl Iterator<Item = Result<Event>>) -> Vec<(u64, String)> {
         events
             .filter_map(|r| {
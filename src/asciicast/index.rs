@@ -0,0 +1,290 @@
+use super::{util, v2, v3, EventData};
+use crate::compress;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const FORMAT_VERSION: u8 = 2;
+const CHECKPOINT_INTERVAL: u64 = 10_000_000;
+
+/// Sidecar index of a recording, mapping recording time to byte offsets of
+/// event lines. It's stored next to the recording, with `.idx` appended to
+/// its filename, and is ignored once the recording gets modified.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Index {
+    version: u8,
+    size: u64,
+    modified: u64,
+    checkpoints: Vec<Checkpoint>,
+}
+
+/// Position of an event line, along with the state needed to resume
+/// parsing from it.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct Checkpoint {
+    /// Time of the event at `offset`.
+    pub time: u64,
+    /// Time of the preceding event, used as a base for v3 intervals.
+    pub base_time: u64,
+    /// Byte offset of the event line.
+    pub offset: u64,
+    /// Number of lines preceding the event line.
+    pub line: usize,
+    /// Terminal size in effect before the event.
+    pub cols: u16,
+    pub rows: u16,
+    /// Screen contents before the event, as a sequence reproducing them.
+    pub screen: String,
+}
+
+enum Format {
+    V2,
+    V3,
+}
+
+pub(crate) fn path_for(path: &Path) -> PathBuf {
+    let mut filename = OsString::from(path.as_os_str());
+    filename.push(".idx");
+
+    filename.into()
+}
+
+impl Index {
+    pub fn build<S: AsRef<Path>>(path: S) -> Result<Self> {
+        let path = path.as_ref();
+
+        if compress::is_compressed(fs::File::open(path)?)? {
+            bail!("compressed recordings can't be indexed");
+        }
+
+        let (size, modified) = stat(path)?;
+        let mut lines = util::Lines::new(io::BufReader::new(fs::File::open(path)?));
+
+        let Some(first_line) = lines.next() else {
+            bail!("empty file");
+        };
+
+        let first_line = first_line.text?;
+
+        let (format, header) = if let Ok(parser) = v2::open(&first_line) {
+            (Format::V2, parser.parse(std::iter::empty()).header)
+        } else if let Ok(parser) = v3::open(&first_line) {
+            (Format::V3, parser.parse(std::iter::empty()).header)
+        } else {
+            bail!("only asciicast v2 and v3 recordings can be indexed");
+        };
+
+        let mut checkpoints = Vec::new();
+        let mut next_checkpoint = CHECKPOINT_INTERVAL;
+        let mut prev_time = 0;
+        let (mut cols, mut rows) = (header.cols, header.rows);
+        let mut vt = super::terminal((cols, rows));
+
+        for line in lines {
+            let (number, offset) = (line.number, line.offset);
+
+            let event = match format {
+                Format::V2 => v2::parse_line(line),
+                Format::V3 => v3::parse_line(line),
+            };
+
+            let Some(event) = event else {
+                continue;
+            };
+
+            let event = event?;
+
+            let time = match format {
+                Format::V2 => event.time,
+                Format::V3 => prev_time + event.time,
+            };
+
+            if time >= next_checkpoint {
+                checkpoints.push(Checkpoint {
+                    time,
+                    base_time: prev_time,
+                    offset,
+                    line: number - 1,
+                    cols,
+                    rows,
+                    screen: vt.dump(),
+                });
+
+                next_checkpoint = (time / CHECKPOINT_INTERVAL + 1) * CHECKPOINT_INTERVAL;
+            }
+
+            if let EventData::Resize(c, r) = event.data {
+                (cols, rows) = (c, r);
+            }
+
+            super::feed(&mut vt, &event.data);
+
+            prev_time = time;
+        }
+
+        Ok(Self {
+            version: FORMAT_VERSION,
+            size,
+            modified,
+            checkpoints,
+        })
+    }
+
+    /// Loads the index of the recording at `path`. Returns `None` when
+    /// there's no index or it's out of date.
+    pub fn load<S: AsRef<Path>>(path: S) -> Result<Option<Self>> {
+        let path = path.as_ref();
+
+        let json = match fs::read_to_string(path_for(path)) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let index: Self = serde_json::from_str(&json)?;
+
+        if index.version != FORMAT_VERSION || (index.size, index.modified) != stat(path)? {
+            return Ok(None);
+        }
+
+        Ok(Some(index))
+    }
+
+    /// Saves the index of the recording at `path`, returning the path of
+    /// the index file.
+    pub fn save<S: AsRef<Path>>(&self, path: S) -> Result<PathBuf> {
+        let index_path = path_for(path.as_ref());
+        fs::write(&index_path, serde_json::to_string(self)?)?;
+
+        Ok(index_path)
+    }
+
+    /// Returns the last checkpoint at or before `time`.
+    pub(crate) fn checkpoint(&self, time: u64) -> Option<&Checkpoint> {
+        let i = self.checkpoints.partition_point(|c| c.time <= time);
+
+        i.checked_sub(1).map(|i| &self.checkpoints[i])
+    }
+
+    pub fn checkpoint_count(&self) -> usize {
+        self.checkpoints.len()
+    }
+}
+
+fn stat(path: &Path) -> Result<(u64, u64)> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?.as_micros() as u64;
+
+    Ok((metadata.len(), modified))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Index;
+    use crate::asciicast::{self, Event, EventData, Header, V3Writer, Writer};
+    use anyhow::Result;
+
+    #[test]
+    fn open_from_path_at() {
+        let events: Vec<Event> = (0..100)
+            .map(|i| match i {
+                42 => Event::resize(i * 1_000_000, (100, 40)),
+                _ => Event::output(i * 1_000_000, format!("{i}\r\n").as_bytes()),
+            })
+            .collect();
+
+        for data in [write(2, &events), write(3, &events)] {
+            let file = tempfile::NamedTempFile::new().unwrap();
+            std::fs::write(file.path(), data).unwrap();
+
+            let scanned = open_at(file.path(), 55_500_000);
+
+            let index = Index::build(file.path()).unwrap();
+            index.save(file.path()).unwrap();
+            assert_eq!(index.checkpoint_count(), 9);
+            assert_eq!(
+                Index::load(file.path())
+                    .unwrap()
+                    .unwrap()
+                    .checkpoint_count(),
+                9
+            );
+
+            let seeked = open_at(file.path(), 55_500_000);
+
+            for (header, events) in [scanned, seeked] {
+                assert_eq!((header.cols, header.rows), (100, 40));
+                assert_eq!(events.len(), 45);
+                assert_eq!(events[0].time, 0);
+                assert_eq!(events[1].time, 500_000);
+                assert!(matches!(&events[1].data, EventData::Output(data) if data == "56\r\n"));
+
+                let mut vt = avt::Vt::new(header.cols as usize, header.rows as usize);
+
+                if let EventData::Output(data) = &events[0].data {
+                    vt.feed_str(data);
+                }
+
+                let text = vt.text();
+                let last = text
+                    .iter()
+                    .rposition(|line| !line.trim().is_empty())
+                    .unwrap();
+
+                assert_eq!(text[last].trim(), "55");
+                assert_eq!(text[last - 1].trim(), "54");
+                assert_eq!(vt.cursor().row, last + 1);
+            }
+
+            std::fs::remove_file(super::path_for(file.path())).unwrap();
+        }
+    }
+
+    #[test]
+    fn stale_index() {
+        let events: Vec<Event> = (0..30)
+            .map(|i| Event::output(i * 1_000_000, b"x"))
+            .collect();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), write(3, &events)).unwrap();
+        Index::build(file.path())
+            .unwrap()
+            .save(file.path())
+            .unwrap();
+
+        std::fs::write(file.path(), write(3, &events[..20])).unwrap();
+
+        assert!(Index::load(file.path()).unwrap().is_none());
+
+        std::fs::remove_file(super::path_for(file.path())).unwrap();
+    }
+
+    fn open_at(path: &std::path::Path, time: u64) -> (Header, Vec<Event>) {
+        let asciicast::Asciicast { header, events, .. } =
+            asciicast::open_from_path_at(path, time).unwrap();
+
+        (header, events.collect::<Result<Vec<Event>>>().unwrap())
+    }
+
+    fn write(version: u8, events: &[Event]) -> Vec<u8> {
+        let header = Header::new(version, 80, 24);
+
+        let mut data = Vec::new();
+
+        if version == 3 {
+            let mut fw = V3Writer::new(&mut data, 0);
+            fw.write_header(&header).unwrap();
+            events.iter().for_each(|e| fw.write_event(e).unwrap());
+        } else {
+            let mut fw = Writer::new(&mut data, 0);
+            fw.write_header(&header).unwrap();
+            events.iter().for_each(|e| fw.write_event(e).unwrap());
+        }
+
+        data
+    }
+}
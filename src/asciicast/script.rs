@@ -0,0 +1,472 @@
+use super::{util, Asciicast, Event, Header};
+use crate::compress;
+use crate::utf8::{self, Chunk, Utf8Decoder};
+use anyhow::{anyhow, bail, Result};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io::{self, BufRead, Read};
+use std::path::Path;
+
+// util-linux script(1) writes session data to a typescript (output log) and,
+// with --log-timing, a timing file describing how it was written. Classic
+// timing lines are `DELAY BYTES`, advanced (multi-stream) ones are
+// `TYPE DELAY ...`, where TYPE is O (output), I (input), S (signal) or H
+// (header).
+const DEFAULT_SIZE: (u16, u16) = (80, 24);
+
+enum Entry {
+    Output(u64, usize),
+    Input(u64, usize),
+    Resize(u64, u16, u16),
+    Exit(u64, i32),
+}
+
+/// Where input entries' data is read from.
+pub enum InputLog {
+    None,
+    Separate(Box<dyn BufRead>),
+    Combined,
+}
+
+struct Events<I> {
+    entries: I,
+    output: Box<dyn BufRead>,
+    input: InputLog,
+    output_decoder: Utf8Decoder,
+    input_decoder: Utf8Decoder,
+    time: u64,
+    queue: VecDeque<Result<Event>>,
+    done: bool,
+}
+
+/// Opens a script(1) session from its timing file, output log (typescript)
+/// and, optionally, input log. The input log may be the same file as the
+/// output log (`script --log-io`).
+pub fn open_from_paths<T, O, I>(
+    timing: T,
+    output: O,
+    input: Option<I>,
+) -> Result<Asciicast<'static>>
+where
+    T: AsRef<Path>,
+    O: AsRef<Path>,
+    I: AsRef<Path>,
+{
+    let open_file = |path: &Path| -> Result<Box<dyn BufRead>> {
+        let file =
+            fs::File::open(path).map_err(|e| anyhow!("can't open {}: {e}", path.display()))?;
+
+        Ok(compress::decoder(file)?)
+    };
+
+    let (output, input) = (output.as_ref(), input.as_ref().map(|p| p.as_ref()));
+    let timing = open_file(timing.as_ref())?;
+
+    let input = match input {
+        Some(input) if same_file(input, output) => InputLog::Combined,
+        Some(input) => InputLog::Separate(open_file(input)?),
+        None => InputLog::None,
+    };
+
+    open(timing, open_file(output)?, input)
+}
+
+/// Opens a script(1) session. Input entries are skipped when there's no
+/// input log.
+pub fn open<'a, T: BufRead + 'a>(
+    timing: T,
+    mut output: Box<dyn BufRead>,
+    mut input: InputLog,
+) -> Result<Asciicast<'a>> {
+    let mut info = script_header(&mut *output)?;
+
+    if let InputLog::Separate(input) = &mut input {
+        script_header(&mut **input)?;
+    }
+
+    let mut entries = util::Lines::new(timing).peekable();
+
+    // advanced format header entries precede all others
+    while let Some(line) = entries.next_if(is_header_line) {
+        let text = line.text?;
+        let mut fields = text.splitn(4, ' ').skip(2);
+
+        if let (Some(name), Some(value)) = (fields.next(), fields.next()) {
+            info.insert(name.to_owned(), value.to_owned());
+        }
+    }
+
+    let cols = info.get("COLUMNS").and_then(|v| v.parse().ok());
+    let rows = info.get("LINES").and_then(|v| v.parse().ok());
+    let term_type = info.get("TERM").cloned();
+
+    let env = ["TERM", "SHELL"]
+        .iter()
+        .filter_map(|name| Some((name.to_string(), info.get(*name)?.clone())))
+        .collect::<HashMap<_, _>>();
+
+    let header = Header {
+        version: 0,
+        cols: cols.unwrap_or(DEFAULT_SIZE.0),
+        rows: rows.unwrap_or(DEFAULT_SIZE.1),
+        term_type,
+        term_version: None,
+        timestamp: info.get("START_TIME").and_then(|t| parse_time(t)),
+        idle_time_limit: None,
+        command: info.get("COMMAND").cloned(),
+        title: None,
+        env: Some(env).filter(|env| !env.is_empty()),
+        theme: None,
+        extra: Default::default(),
+    };
+
+    let mut prev_time = 0;
+
+    let entries = entries.filter_map(move |line| {
+        let entry = parse_entry(&line, &mut prev_time).map_err(|e| line.error(e));
+
+        entry.transpose().map(|entry| (line, entry))
+    });
+
+    let events = Box::new(Events {
+        entries,
+        output,
+        input,
+        output_decoder: Utf8Decoder::default(),
+        input_decoder: Utf8Decoder::default(),
+        time: 0,
+        queue: VecDeque::new(),
+        done: false,
+    });
+
+    Ok(Asciicast {
+        header,
+        events,
+        position: Default::default(),
+    })
+}
+
+/// Skips the `Script started on ...` line of a log, returning the terminal
+/// info it contains, e.g. `[TERM="xterm" TTY="/dev/pts/1" COLUMNS="80"
+/// LINES="24"]`.
+fn script_header(log: &mut dyn BufRead) -> io::Result<HashMap<String, String>> {
+    let mut info = HashMap::new();
+
+    if !log.fill_buf()?.starts_with(b"Script started on ") {
+        return Ok(info);
+    }
+
+    let mut line = Vec::new();
+    log.read_until(b'\n', &mut line)?;
+    let line = String::from_utf8_lossy(&line);
+
+    if let Some(time) = line.strip_prefix("Script started on ") {
+        info.insert("START_TIME".to_owned(), time.chars().take(25).collect());
+    }
+
+    if let Some((_, attrs)) = line.trim_end().trim_end_matches(']').split_once('[') {
+        for attr in attrs.split(' ') {
+            if let Some((name, value)) = attr.split_once('=') {
+                info.insert(name.to_owned(), value.trim_matches('"').to_owned());
+            }
+        }
+    }
+
+    Ok(info)
+}
+
+/// Parses `YYYY-MM-DD HH:MM:SS+HH:MM` into a Unix timestamp.
+fn parse_time(time: &str) -> Option<u64> {
+    let field = |range: std::ops::Range<usize>| time.get(range)?.parse::<i64>().ok();
+
+    let (year, month, day) = (field(0..4)?, field(5..7)?, field(8..10)?);
+    let (hour, min, sec) = (field(11..13)?, field(14..16)?, field(17..19)?);
+    let offset = field(20..22)? * 3600 + field(23..25).or(field(22..24))? * 60;
+
+    let offset = match time.get(19..20)? {
+        "+" => offset,
+        "-" => -offset,
+        _ => return None,
+    };
+
+    // days since 1970-01-01 in the proleptic Gregorian calendar
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * m + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    u64::try_from(days * 86400 + hour * 3600 + min * 60 + sec - offset).ok()
+}
+
+/// Formats a Unix timestamp as `YYYY-MM-DD HH:MM:SS+00:00`, the way
+/// script(1) does in log headers.
+pub(crate) fn format_time(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let secs = timestamp % 86400;
+
+    // inverse of the conversion in parse_time
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}+00:00",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn is_header_line(line: &util::Line) -> bool {
+    line.text.as_ref().is_ok_and(|text| text.starts_with("H "))
+}
+
+fn parse_entry(line: &util::Line, prev_time: &mut u64) -> Result<Option<Entry>> {
+    let text = match &line.text {
+        Ok(text) => text.trim(),
+        Err(e) => bail!("{e}"),
+    };
+
+    if text.is_empty() {
+        return Ok(None);
+    }
+
+    let fields: Vec<&str> = text.split_whitespace().collect();
+
+    let (kind, fields) = match fields[0] {
+        "O" | "I" | "S" | "H" => (fields[0], &fields[1..]),
+        _ => ("O", &fields[..]),
+    };
+
+    let delay = fields.first().ok_or(anyhow!("missing delay"))?;
+    let delay: f64 = delay.parse().map_err(|e| anyhow!("invalid delay: {e}"))?;
+
+    if !delay.is_finite() || delay < 0.0 {
+        bail!("invalid delay: {delay}");
+    }
+
+    let time = *prev_time + (delay * 1_000_000.0).round() as u64;
+    *prev_time = time;
+
+    let len = || -> Result<usize> {
+        let len = fields.get(1).ok_or(anyhow!("missing byte count"))?;
+
+        len.parse().map_err(|e| anyhow!("invalid byte count: {e}"))
+    };
+
+    match kind {
+        "O" => Ok(Some(Entry::Output(time, len()?))),
+        "I" => Ok(Some(Entry::Input(time, len()?))),
+
+        "S" if fields.get(1) == Some(&"SIGWINCH") => {
+            let param = |name: &str| {
+                fields[2..]
+                    .iter()
+                    .find_map(|f| f.strip_prefix(name)?.strip_prefix('='))
+                    .and_then(|v| v.parse().ok())
+                    .ok_or(anyhow!("missing {name} in SIGWINCH entry"))
+            };
+
+            Ok(Some(Entry::Resize(time, param("COLS")?, param("ROWS")?)))
+        }
+
+        "H" if fields.get(1) == Some(&"EXIT_CODE") => {
+            let status = fields
+                .get(2)
+                .and_then(|s| s.parse().ok())
+                .ok_or(anyhow!("invalid exit code"))?;
+
+            Ok(Some(Entry::Exit(time, status)))
+        }
+
+        _ => Ok(None),
+    }
+}
+
+fn read_data(log: &mut dyn BufRead, len: usize) -> Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(len);
+    log.take(len as u64).read_to_end(&mut data)?;
+
+    if data.len() < len {
+        bail!(
+            "log ended prematurely ({} of {} bytes read)",
+            data.len(),
+            len
+        );
+    }
+
+    Ok(data)
+}
+
+impl<I: Iterator<Item = (util::Line, Result<Entry>)>> Events<I> {
+    fn push_entry(&mut self, line: &util::Line, entry: Entry) -> Result<()> {
+        match entry {
+            Entry::Output(time, len) => {
+                self.time = time;
+                let data = read_data(&mut self.output, len).map_err(|e| line.error(e))?;
+
+                for chunk in self.output_decoder.feed(&data) {
+                    self.queue.push_back(Ok(match chunk {
+                        Chunk::Text(text) => Event::output(time, text.as_bytes()),
+                        Chunk::Invalid(bytes) => Event::bytes(time, &bytes),
+                    }));
+                }
+            }
+
+            Entry::Input(time, len) => {
+                self.time = time;
+
+                let log = match &mut self.input {
+                    InputLog::Separate(input) => input,
+                    InputLog::Combined => &mut self.output,
+                    InputLog::None => return Ok(()),
+                };
+
+                let data = read_data(log, len).map_err(|e| line.error(e))?;
+                let chunks = self.input_decoder.feed(&data);
+
+                if !chunks.is_empty() {
+                    let text = utf8::join_lossy(chunks);
+                    self.queue
+                        .push_back(Ok(Event::input(time, text.as_bytes())));
+                }
+            }
+
+            Entry::Resize(time, cols, rows) => {
+                self.time = time;
+                self.queue.push_back(Ok(Event::resize(time, (cols, rows))));
+            }
+
+            Entry::Exit(time, status) => {
+                self.time = time;
+                self.queue.push_back(Ok(Event::exit(time, status)));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<I: Iterator<Item = (util::Line, Result<Entry>)>> Iterator for Events<I> {
+    type Item = Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.queue.is_empty() && !self.done {
+            match self.entries.next() {
+                Some((line, Ok(entry))) => {
+                    if let Err(e) = self.push_entry(&line, entry) {
+                        self.queue.push_back(Err(e));
+                    }
+                }
+
+                Some((_, Err(e))) => self.queue.push_back(Err(e)),
+
+                None => {
+                    if let Some(bytes) = self.output_decoder.finish() {
+                        self.queue.push_back(Ok(Event::bytes(self.time, &bytes)));
+                    }
+
+                    self.done = true;
+                }
+            }
+        }
+
+        self.queue.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InputLog;
+    use crate::asciicast::{Asciicast, Event, Header};
+    use anyhow::Result;
+
+    fn open(
+        timing: &'static str,
+        output: &'static str,
+        input: Option<&'static str>,
+    ) -> (Header, Vec<Event>) {
+        let input = match input {
+            Some(input) => InputLog::Separate(Box::new(input.as_bytes())),
+            None => InputLog::None,
+        };
+
+        let Asciicast { header, events, .. } =
+            super::open(timing.as_bytes(), Box::new(output.as_bytes()), input).unwrap();
+
+        (header, events.collect::<Result<Vec<Event>>>().unwrap())
+    }
+
+    #[test]
+    fn classic() {
+        let output = "Script started on 2024-01-01 12:00:00+00:00 [TERM=\"xterm-256color\" TTY=\"/dev/pts/1\" COLUMNS=\"100\" LINES=\"30\"]\n$ ls\r\nfoo\r\n\nScript done on 2024-01-01 12:00:05+00:00 [COMMAND_EXIT_CODE=\"0\"]\n";
+        let timing = "0.5 2\n1.25 4\n0.000001 5\n";
+        let (header, events) = open(timing, output, None);
+
+        assert_eq!((header.cols, header.rows), (100, 30));
+        assert_eq!(header.term_type.as_deref(), Some("xterm-256color"));
+        assert_eq!(header.timestamp, Some(1704110400));
+
+        assert_eq!(
+            events,
+            vec![
+                Event::output(500_000, b"$ "),
+                Event::output(1_750_000, b"ls\r\n"),
+                Event::output(1_750_001, b"foo\r\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn time() {
+        for timestamp in [0, 951782400, 1704110400, 4102444799] {
+            let formatted = super::format_time(timestamp);
+
+            assert_eq!(super::parse_time(&formatted), Some(timestamp));
+        }
+
+        assert_eq!(super::format_time(1704110400), "2024-01-01 12:00:00+00:00");
+    }
+
+    #[test]
+    fn advanced() {
+        let output = "Script started on 2024-01-01 12:00:00+00:00 [COMMAND=\"bash\"]\n$ ls\r\n";
+        let input = "Script started on 2024-01-01 12:00:00+00:00 [COMMAND=\"bash\"]\nls\r";
+        let timing = "H 0.000000 START_TIME 2024-01-01 13:00:00+01:00\nH 0.000000 COLUMNS 120\nH 0.000000 LINES 40\nH 0.000000 COMMAND bash -l\nO 0.5 2\nI 1.0 3\nO 0.1 2\nS 0.2 SIGWINCH ROWS=50 COLS=150\nH 0.3 EXIT_CODE 1\n";
+        let (header, events) = open(timing, output, Some(input));
+
+        assert_eq!((header.cols, header.rows), (120, 40));
+        assert_eq!(header.command.as_deref(), Some("bash -l"));
+        assert_eq!(header.timestamp, Some(1704110400));
+
+        assert_eq!(
+            events,
+            vec![
+                Event::output(500_000, b"$ "),
+                Event::input(1_500_000, b"ls\r"),
+                Event::output(1_600_000, b"ls"),
+                Event::resize(1_800_000, (150, 50)),
+                Event::exit(2_100_000, 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,379 @@
+use super::{Asciicast, Event, Header, ParseError};
+use crate::utf8::{Chunk, Utf8Decoder};
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::io::{self, Read};
+
+// A ttyrec file is a sequence of frames, each being a 12 byte header (sec,
+// usec and data length, as little-endian u32) followed by output data.
+pub const HEADER_LEN: usize = 12;
+const MAX_FRAME_LEN: u32 = 16 * 1024 * 1024;
+const SIZE_LOOKAHEAD: usize = 1024 * 1024;
+const DEFAULT_SIZE: (u16, u16) = (80, 24);
+
+struct Frame {
+    time: u64,
+    data: Vec<u8>,
+}
+
+struct Frames<R> {
+    reader: R,
+    number: usize,
+    offset: u64,
+    done: bool,
+}
+
+struct Events<I> {
+    frames: I,
+    decoder: Utf8Decoder,
+    start_time: Option<u64>,
+    time: u64,
+    size: (u16, u16),
+    queue: VecDeque<Result<Event>>,
+    done: bool,
+}
+
+/// Checks whether the data looks like the beginning of a ttyrec file.
+pub fn sniff(data: &[u8]) -> bool {
+    match parse_header(data) {
+        Some((_, usec, len)) => usec < 1_000_000 && len <= MAX_FRAME_LEN,
+        None => false,
+    }
+}
+
+/// Opens a ttyrec recording. Terminal size is taken from the first resize
+/// sequence (`\e[8;ROWS;COLSt`) found near the beginning of the recording,
+/// falling back to 80x24. Later resize sequences become resize events.
+pub fn open<'a, R: Read + 'a>(reader: R) -> Result<Asciicast<'a>> {
+    let mut frames = Frames {
+        reader,
+        number: 0,
+        offset: 0,
+        done: false,
+    };
+
+    let mut lookahead = Vec::new();
+    let mut lookahead_len = 0;
+    let mut size = None;
+
+    while size.is_none() && lookahead_len < SIZE_LOOKAHEAD {
+        match frames.next() {
+            Some(Ok(frame)) => {
+                size = find_resize(&frame.data);
+                lookahead_len += frame.data.len();
+                lookahead.push(Ok(frame));
+            }
+
+            Some(Err(e)) => {
+                lookahead.push(Err(e));
+                break;
+            }
+
+            None => break,
+        }
+    }
+
+    let start_time = lookahead
+        .iter()
+        .find_map(|frame| frame.as_ref().ok())
+        .map(|frame| frame.time);
+
+    let (cols, rows) = size.unwrap_or(DEFAULT_SIZE);
+
+    let header = Header {
+        version: 0,
+        cols,
+        rows,
+        term_type: None,
+        term_version: None,
+        timestamp: start_time.map(|time| time / 1_000_000).filter(|t| *t > 0),
+        idle_time_limit: None,
+        command: None,
+        title: None,
+        env: None,
+        theme: None,
+        extra: Default::default(),
+    };
+
+    let events = Box::new(Events {
+        frames: lookahead.into_iter().chain(frames),
+        decoder: Utf8Decoder::default(),
+        start_time,
+        time: 0,
+        size: (cols, rows),
+        queue: VecDeque::new(),
+        done: false,
+    });
+
+    Ok(Asciicast {
+        header,
+        events,
+        position: Default::default(),
+    })
+}
+
+fn parse_header(data: &[u8]) -> Option<(u32, u32, u32)> {
+    let field = |i: usize| {
+        data.get(i * 4..i * 4 + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+
+    Some((field(0)?, field(1)?, field(2)?))
+}
+
+/// Finds the first `\e[8;ROWS;COLSt` sequence, returning (cols, rows).
+fn find_resize(data: &[u8]) -> Option<(u16, u16)> {
+    find_resizes(data).next()
+}
+
+fn find_resizes(data: &[u8]) -> impl Iterator<Item = (u16, u16)> + '_ {
+    data.windows(4)
+        .enumerate()
+        .filter(|(_, w)| *w == b"\x1b[8;")
+        .filter_map(|(i, _)| {
+            let rest = &data[i + 4..];
+            let end = rest.iter().position(|b| *b == b't')?;
+            let params = std::str::from_utf8(&rest[..end]).ok()?;
+            let (rows, cols) = params.split_once(';')?;
+
+            Some((cols.parse().ok()?, rows.parse().ok()?))
+        })
+}
+
+impl<R: Read> Frames<R> {
+    fn error(&self, offset: u64, message: &str) -> anyhow::Error {
+        ParseError {
+            line: self.number,
+            column: None,
+            offset,
+            message: message.to_owned(),
+        }
+        .into()
+    }
+}
+
+impl<R: Read> Iterator for Frames<R> {
+    type Item = Result<Frame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let offset = self.offset;
+        self.number += 1;
+        let mut header = [0; HEADER_LEN];
+
+        match read_full(&mut self.reader, &mut header) {
+            Ok(0) => {
+                self.done = true;
+
+                return None;
+            }
+
+            Ok(HEADER_LEN) => (),
+
+            Ok(_) => {
+                self.done = true;
+
+                return Some(Err(self.error(offset, "truncated frame header")));
+            }
+
+            Err(e) => {
+                self.done = true;
+
+                return Some(Err(e.into()));
+            }
+        }
+
+        let (sec, usec, len) = parse_header(&header).unwrap();
+
+        if len > MAX_FRAME_LEN {
+            self.done = true;
+
+            return Some(Err(self.error(offset, "invalid frame length")));
+        }
+
+        let mut data = vec![0; len as usize];
+
+        match read_full(&mut self.reader, &mut data) {
+            Ok(n) if n == data.len() => (),
+
+            Ok(_) => {
+                self.done = true;
+
+                return Some(Err(self.error(offset, "truncated frame data")));
+            }
+
+            Err(e) => {
+                self.done = true;
+
+                return Some(Err(e.into()));
+            }
+        }
+
+        self.offset += (HEADER_LEN + data.len()) as u64;
+
+        Some(Ok(Frame {
+            time: sec as u64 * 1_000_000 + usec as u64,
+            data,
+        }))
+    }
+}
+
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+
+    while n < buf.len() {
+        match reader.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(m) => n += m,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(n)
+}
+
+impl<I: Iterator<Item = Result<Frame>>> Events<I> {
+    fn push_frame(&mut self, frame: Frame) {
+        let start_time = *self.start_time.get_or_insert(frame.time);
+        self.time = frame.time.saturating_sub(start_time);
+
+        for chunk in self.decoder.feed(&frame.data) {
+            self.queue.push_back(Ok(match chunk {
+                Chunk::Text(text) => Event::output(self.time, text.as_bytes()),
+                Chunk::Invalid(bytes) => Event::bytes(self.time, &bytes),
+            }));
+        }
+
+        for size in find_resizes(&frame.data) {
+            if size != self.size {
+                self.queue.push_back(Ok(Event::resize(self.time, size)));
+                self.size = size;
+            }
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<Frame>>> Iterator for Events<I> {
+    type Item = Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.queue.is_empty() && !self.done {
+            match self.frames.next() {
+                Some(Ok(frame)) => self.push_frame(frame),
+                Some(Err(e)) => self.queue.push_back(Err(e)),
+
+                None => {
+                    if let Some(bytes) = self.decoder.finish() {
+                        self.queue.push_back(Ok(Event::bytes(self.time, &bytes)));
+                    }
+
+                    self.done = true;
+                }
+            }
+        }
+
+        self.queue.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asciicast::{self, Asciicast, Event, EventData};
+    use anyhow::Result;
+    use std::io;
+
+    fn frame(sec: u32, usec: u32, data: &[u8]) -> Vec<u8> {
+        let mut frame = Vec::new();
+        frame.extend_from_slice(&sec.to_le_bytes());
+        frame.extend_from_slice(&usec.to_le_bytes());
+        frame.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        frame.extend_from_slice(data);
+
+        frame
+    }
+
+    #[test]
+    fn open() {
+        let data = [
+            frame(1700000000, 500_000, b"\x1b[8;30;100t$ "),
+            frame(1700000001, 0, "ża".as_bytes()),
+            frame(1700000001, 250_000, &"żó".as_bytes()[..1]),
+            frame(1700000002, 0, &"żó".as_bytes()[1..]),
+            frame(1700000003, 0, b"\x1b[8;40;120t"),
+        ]
+        .concat();
+
+        let Asciicast { header, events, .. } = asciicast::open(&data[..]).unwrap();
+        let events = events.collect::<Result<Vec<Event>>>().unwrap();
+
+        assert_eq!((header.cols, header.rows), (100, 30));
+        assert_eq!(header.timestamp, Some(1700000000));
+        assert_eq!(events[0].time, 0);
+        assert!(matches!(&events[0].data, EventData::Output(s) if s == "\x1b[8;30;100t$ "));
+        assert!(matches!(&events[1].data, EventData::Output(s) if s == "ża"));
+        assert_eq!(events[1].time, 500_000);
+        assert!(matches!(&events[2].data, EventData::Output(s) if s == "żó"));
+        assert_eq!(events[2].time, 1_500_000);
+        assert!(matches!(events[4].data, EventData::Resize(120, 40)));
+        assert_eq!(events.len(), 5);
+    }
+
+    #[test]
+    fn open_small_reads() {
+        let data = [frame(1, 0, b"hello"), frame(2, 0, b"world")].concat();
+        let reader = io::BufReader::with_capacity(1, &data[..]);
+
+        let Asciicast { header, events, .. } = asciicast::open(reader).unwrap();
+        let events = events.collect::<Result<Vec<Event>>>().unwrap();
+
+        assert_eq!(header.version, 0);
+        assert_eq!(events.len(), 2);
+        assert!(matches!(&events[0].data, EventData::Output(s) if s == "hello"));
+
+        let data = "{\"version\":2,\"width\":80,\"height\":24}\n[1.0,\"o\",\"hello\"]\n";
+        let reader = io::BufReader::with_capacity(1, data.as_bytes());
+
+        let Asciicast { header, events, .. } = asciicast::open(reader).unwrap();
+
+        assert_eq!(header.version, 2);
+        assert_eq!(events.count(), 1);
+    }
+
+    #[test]
+    fn default_size() {
+        let data = frame(1, 0, b"hello");
+        let Asciicast { header, .. } = asciicast::open(&data[..]).unwrap();
+
+        assert_eq!((header.cols, header.rows), (80, 24));
+    }
+
+    #[test]
+    fn truncated() {
+        let mut data = [frame(1, 0, b"hello"), frame(2, 0, b"world")].concat();
+        data.truncate(data.len() - 2);
+
+        let Asciicast { events, .. } = asciicast::open(&data[..]).unwrap();
+        let events = events.collect::<Vec<_>>();
+
+        assert_eq!(events.len(), 2);
+        assert!(events[0].is_ok());
+
+        let e = events[1].as_ref().err().unwrap();
+        let e = e.downcast_ref::<asciicast::ParseError>().unwrap();
+
+        assert_eq!(e.line, 2);
+        assert_eq!(e.offset, 17);
+    }
+
+    #[test]
+    fn sniff() {
+        assert!(super::sniff(&frame(1700000000, 999_999, b"foo")));
+        assert!(!super::sniff(b"{\"version\": 2, \"width\": 80}"));
+        assert!(!super::sniff(b"Script started on 2024-01-01"));
+        assert!(!super::sniff(b"[1.0,"));
+    }
+}
@@ -1,5 +1,16 @@
-use anyhow::Result;
-use serde::{Deserialize, Deserializer};
+use super::{ParseError, Theme};
+use crate::logger;
+use anyhow::{anyhow, bail, Result};
+use rgb::RGB8;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt::Display;
+use std::io::{self, BufRead};
+use std::string::FromUtf8Error;
+
+/// Code of events with base64 encoded raw output (not valid UTF-8). It's
+/// not part of the asciicast spec, hence the prefix, keeping it apart from
+/// other tools' custom codes.
+pub const BYTES_CODE: &str = "asciinema.b";
 
 pub fn deserialize_time<'de, D>(deserializer: D) -> Result<u64, D::Error>
 where
@@ -37,3 +48,261 @@ where
         _ => Err(Error::custom(format!("invalid time format: {value}"))),
     }
 }
+
+pub fn format_time(time: u64) -> String {
+    let formatted = format!("{}.{:0>6}", time / 1_000_000, time % 1_000_000);
+    let trimmed = formatted.trim_end_matches('0');
+
+    if trimmed.ends_with('.') {
+        format!("{trimmed}0")
+    } else {
+        trimmed.to_owned()
+    }
+}
+
+/// Returns extra header fields, except for ones named like standard fields
+/// of the format (they'd end up as duplicate keys).
+pub fn extra_fields(
+    extra: &serde_json::Map<String, serde_json::Value>,
+    fields: &[&str],
+) -> serde_json::Map<String, serde_json::Value> {
+    extra
+        .iter()
+        .filter(|(key, _)| !fields.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+pub struct Line {
+    pub number: usize,
+    pub offset: u64,
+    pub text: io::Result<String>,
+}
+
+impl Line {
+    pub fn error<E: Display>(&self, e: E) -> anyhow::Error {
+        self.error_at(None, e)
+    }
+
+    pub fn error_at<E: Display>(&self, column: Option<usize>, e: E) -> anyhow::Error {
+        ParseError {
+            line: self.number,
+            column,
+            offset: self.offset,
+            message: e.to_string(),
+        }
+        .into()
+    }
+
+    /// Takes the text of the line, or the error from reading it. Invalid
+    /// UTF-8 becomes a parse error pointing at the first invalid byte, and
+    /// so does a truncated or corrupt compressed stream (e.g. of an
+    /// interrupted recording), which is where reading ends. Other I/O errors
+    /// are returned as is.
+    pub fn take_text(&mut self) -> Result<String> {
+        match std::mem::replace(&mut self.text, Ok(String::new())) {
+            Ok(text) => Ok(text),
+
+            Err(e) => match e.get_ref().and_then(|e| e.downcast_ref::<FromUtf8Error>()) {
+                Some(utf8) => Err(self.error_at(
+                    Some(utf8.utf8_error().valid_up_to() + 1),
+                    "invalid UTF-8 sequence",
+                )),
+
+                // decompressors report truncated and corrupt data this way
+                None if matches!(
+                    e.kind(),
+                    io::ErrorKind::UnexpectedEof | io::ErrorKind::InvalidInput
+                ) =>
+                {
+                    Err(self.error(format!("damaged compressed data: {e}")))
+                }
+
+                None => Err(e.into()),
+            },
+        }
+    }
+
+    /// Converts an error from parsing the line, pointing at the column
+    /// reported by the JSON parser.
+    pub fn parse_error(&self, e: anyhow::Error) -> anyhow::Error {
+        match e.downcast_ref::<serde_json::Error>() {
+            Some(e) if e.line() > 0 => self.error_at(Some(e.column()), json_message(e)),
+            _ => self.error(e),
+        }
+    }
+}
+
+/// Returns the message of a JSON error without its position suffix.
+pub fn json_message(e: &serde_json::Error) -> String {
+    let message = e.to_string();
+    let suffix = format!(" at line {} column {}", e.line(), e.column());
+
+    message
+        .strip_suffix(&suffix)
+        .map(str::to_owned)
+        .unwrap_or(message)
+}
+
+pub struct Lines<R> {
+    reader: R,
+    number: usize,
+    offset: u64,
+    done: bool,
+}
+
+impl<R: BufRead> Lines<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            number: 0,
+            offset: 0,
+            done: false,
+        }
+    }
+
+    /// Continues numbering from a known position, for readers that were
+    /// seeked past `number` lines ending at byte `offset`.
+    pub fn starting_at(reader: R, number: usize, offset: u64) -> Self {
+        Self {
+            reader,
+            number,
+            offset,
+            done: false,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Lines<R> {
+    type Item = Line;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let mut buf = Vec::new();
+        let number = self.number + 1;
+        let offset = self.offset;
+
+        match self.reader.read_until(b'\n', &mut buf) {
+            Ok(0) => {
+                self.done = true;
+
+                None
+            }
+
+            Ok(n) => {
+                self.number = number;
+                self.offset += n as u64;
+
+                if buf.ends_with(b"\n") {
+                    buf.pop();
+
+                    if buf.ends_with(b"\r") {
+                        buf.pop();
+                    }
+                }
+
+                let text = String::from_utf8(buf)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+
+                Some(Line {
+                    number,
+                    offset,
+                    text,
+                })
+            }
+
+            Err(e) => {
+                self.done = true;
+
+                Some(Line {
+                    number,
+                    offset,
+                    text: Err(e),
+                })
+            }
+        }
+    }
+}
+
+/// Deserializes an optional theme, ignoring (with a warning) an invalid
+/// one, as it's not essential for playback.
+pub fn deserialize_theme<'de, D>(deserializer: D) -> Result<Option<Theme>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value: Option<serde_json::Value> = Deserialize::deserialize(deserializer)?;
+
+    match value.map(Theme::deserialize).transpose() {
+        Ok(theme) => Ok(theme),
+
+        Err(e) => {
+            logger::warn!("Ignoring invalid theme: {}", e);
+
+            Ok(None)
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ThemeJson {
+    fg: String,
+    bg: String,
+    palette: String,
+}
+
+impl TryFrom<ThemeJson> for Theme {
+    type Error = anyhow::Error;
+
+    fn try_from(json: ThemeJson) -> Result<Self> {
+        let palette = json
+            .palette
+            .split(':')
+            .map(parse_hex_color)
+            .collect::<Result<Vec<_>>>()?;
+
+        if palette.len() != 8 && palette.len() != 16 {
+            bail!("palette must have 8 or 16 colors, got {}", palette.len());
+        }
+
+        Ok(Theme {
+            fg: parse_hex_color(&json.fg)?,
+            bg: parse_hex_color(&json.bg)?,
+            palette,
+        })
+    }
+}
+
+impl From<Theme> for ThemeJson {
+    fn from(theme: Theme) -> Self {
+        ThemeJson {
+            fg: format_hex_color(theme.fg),
+            bg: format_hex_color(theme.bg),
+            palette: theme
+                .palette
+                .iter()
+                .map(|c| format_hex_color(*c))
+                .collect::<Vec<_>>()
+                .join(":"),
+        }
+    }
+}
+
+fn parse_hex_color(s: &str) -> Result<RGB8> {
+    let hex = s
+        .strip_prefix('#')
+        .filter(|hex| hex.len() == 6 && hex.is_ascii())
+        .ok_or(anyhow!("invalid color: {s}"))?;
+
+    let channel = |i: usize| {
+        u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| anyhow!("invalid color: {s}"))
+    };
+
+    Ok(RGB8::new(channel(0)?, channel(2)?, channel(4)?))
+}
+
+fn format_hex_color(color: RGB8) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
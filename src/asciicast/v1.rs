@@ -1,18 +1,21 @@
-use super::{Asciicast, Event, Header};
-use crate::asciicast::util::deserialize_time;
-use anyhow::{bail, Result};
-use serde::Deserialize;
-use std::collections::HashMap;
+use super::{Asciicast, Event, EventData, Header, ParseError};
+use crate::asciicast::util::{self, deserialize_time};
+use crate::logger;
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
 
 #[derive(Debug, Deserialize)]
-struct V1 {
+struct V1<E = V1OutputEvent> {
     version: u8,
     width: u16,
     height: u16,
     command: Option<String>,
     title: Option<String>,
     env: Option<HashMap<String, String>>,
-    stdout: Vec<V1OutputEvent>,
+    stdout: Vec<E>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -23,7 +26,7 @@ struct V1OutputEvent {
 }
 
 pub fn load(json: String) -> Result<Asciicast<'static>> {
-    let asciicast: V1 = serde_json::from_str(&json)?;
+    let asciicast: V1 = serde_json::from_str(&json).map_err(|e| json_error(&json, e))?;
 
     if asciicast.version != 1 {
         bail!("unsupported asciicast version")
@@ -33,19 +36,284 @@ pub fn load(json: String) -> Result<Asciicast<'static>> {
         version: 1,
         cols: asciicast.width,
         rows: asciicast.height,
+        term_type: asciicast
+            .env
+            .as_ref()
+            .and_then(|env| env.get("TERM"))
+            .cloned(),
+        term_version: None,
         timestamp: None,
         idle_time_limit: None,
         command: asciicast.command.clone(),
         title: asciicast.title.clone(),
         env: asciicast.env.clone(),
+        theme: None,
+        extra: Default::default(),
     };
 
-    let events = Box::new(
-        asciicast
-            .stdout
-            .into_iter()
-            .map(|e| Ok(Event::output(e.time, e.data.as_bytes()))),
-    );
+    // frame times in v1 are delays since the preceding frame
+    let mut time = 0;
 
-    Ok(Asciicast { header, events })
+    let events = Box::new(asciicast.stdout.into_iter().map(move |e| {
+        time += e.time;
+
+        Ok(Event::output(time, e.data.as_bytes()))
+    }));
+
+    Ok(Asciicast {
+        header,
+        events,
+        position: Default::default(),
+    })
+}
+
+/// Writes a v1 file, with the delay of each output frame relative to the
+/// preceding one. The recording is a single JSON document, so it's written
+/// out in `finish`. Only output can be represented in v1, other events are
+/// skipped, with a warning about them printed in `finish`.
+pub struct Writer<W: Write> {
+    writer: W,
+    header: Option<Header>,
+    stdout: Vec<(f64, String)>,
+    prev_time: u64,
+    duration: u64,
+    unsupported: BTreeMap<&'static str, usize>,
+}
+
+#[derive(Serialize)]
+struct V1Document<'a> {
+    version: u8,
+    width: u16,
+    height: u16,
+    duration: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    command: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+    env: HashMap<String, String>,
+    stdout: &'a [(f64, String)],
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            header: None,
+            stdout: Vec::new(),
+            prev_time: 0,
+            duration: 0,
+            unsupported: BTreeMap::new(),
+        }
+    }
+
+    pub fn write_header(&mut self, header: &Header) -> io::Result<()> {
+        self.header = Some(header.clone());
+
+        Ok(())
+    }
+
+    pub fn write_event(&mut self, event: &Event) -> io::Result<()> {
+        self.duration = self.duration.max(event.time);
+
+        if let Some(kind) = unsupported(&event.data) {
+            *self.unsupported.entry(kind).or_default() += 1;
+        }
+
+        let data = match &event.data {
+            EventData::Output(data) => data.clone(),
+            EventData::Bytes(data) => String::from_utf8_lossy(data).to_string(),
+            _ => return Ok(()),
+        };
+
+        // time of skipped events goes into the delay of the next frame
+        let delay = event.time.saturating_sub(self.prev_time);
+        self.prev_time = self.prev_time.max(event.time);
+        self.stdout.push((delay as f64 / 1_000_000.0, data));
+
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        for (kind, count) in std::mem::take(&mut self.unsupported) {
+            logger::warn!(
+                "asciicast v1 can't represent {} events ({} in the recording)",
+                kind,
+                count
+            );
+        }
+
+        let header = self.header.take().expect("header should be written first");
+        let mut env = header.env.clone().unwrap_or_default();
+
+        if let Some(term_type) = &header.term_type {
+            env.entry("TERM".to_owned())
+                .or_insert_with(|| term_type.clone());
+        }
+
+        let doc = V1Document {
+            version: 1,
+            width: header.cols,
+            height: header.rows,
+            duration: self.duration as f64 / 1_000_000.0,
+            command: header.command.as_deref(),
+            title: header.title.as_deref(),
+            env,
+            stdout: &self.stdout,
+        };
+
+        serde_json::to_writer(&mut self.writer, &doc)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+}
+
+/// Names the kind of event data v1 can't represent (fully), if any.
+fn unsupported(data: &EventData) -> Option<&'static str> {
+    match data {
+        EventData::Output(_) => None,
+        EventData::Bytes(data) if std::str::from_utf8(data).is_ok() => None,
+        EventData::Bytes(_) => Some("non-UTF-8 output"),
+        EventData::Input(_) => Some("input"),
+        EventData::Resize(_, _) => Some("resize"),
+        EventData::Marker(_) => Some("marker"),
+        EventData::Exit(_) => Some("exit"),
+        EventData::Other(_, _) => Some("custom"),
+    }
+}
+
+/// Checks the document for problems, reporting all invalid frames (`load`
+/// stops at the first one).
+pub fn validate(json: &str) -> Vec<ParseError> {
+    let asciicast: V1<&RawValue> = match serde_json::from_str(json) {
+        Ok(asciicast) => asciicast,
+        Err(e) => return vec![parse_error(json, e)],
+    };
+
+    if asciicast.version != 1 {
+        return vec![error_at(
+            json,
+            0,
+            "unsupported asciicast version".to_owned(),
+        )];
+    }
+
+    asciicast
+        .stdout
+        .iter()
+        .filter_map(|frame| {
+            let e = serde_json::from_str::<V1OutputEvent>(frame.get()).err()?;
+            let start = frame.get().as_ptr() as usize - json.as_ptr() as usize;
+            let offset = start + error_offset(frame.get(), &e);
+
+            Some(error_at(json, offset, util::json_message(&e)))
+        })
+        .collect()
+}
+
+fn json_error(json: &str, e: serde_json::Error) -> anyhow::Error {
+    if e.line() == 0 {
+        return anyhow!(e);
+    }
+
+    parse_error(json, e).into()
+}
+
+fn parse_error(json: &str, e: serde_json::Error) -> ParseError {
+    error_at(json, error_offset(json, &e), util::json_message(&e))
+}
+
+/// Returns the byte offset of the position a JSON error points at.
+fn error_offset(json: &str, e: &serde_json::Error) -> usize {
+    let line_offset: usize = json
+        .split('\n')
+        .take(e.line().saturating_sub(1))
+        .map(|l| l.len() + 1)
+        .sum();
+
+    line_offset + e.column().saturating_sub(1)
+}
+
+fn error_at(json: &str, offset: usize, message: String) -> ParseError {
+    let before = &json.as_bytes()[..offset.min(json.len())];
+    let line_start = before
+        .iter()
+        .rposition(|b| *b == b'\n')
+        .map_or(0, |i| i + 1);
+
+    ParseError {
+        line: before.iter().filter(|b| **b == b'\n').count() + 1,
+        column: Some(offset - line_start + 1),
+        offset: offset as u64,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Writer;
+    use crate::asciicast::{self, Event, EventData, Header};
+    use std::collections::HashMap;
+
+    #[test]
+    fn writer() {
+        let header = Header {
+            version: 2,
+            cols: 80,
+            rows: 24,
+            term_type: Some("xterm-256color".to_owned()),
+            term_version: None,
+            timestamp: Some(1700000000),
+            idle_time_limit: None,
+            command: None,
+            title: Some("Demo".to_owned()),
+            env: Some(HashMap::from([("SHELL".to_owned(), "/bin/zsh".to_owned())])),
+            theme: None,
+            extra: Default::default(),
+        };
+
+        let mut data = Vec::new();
+        let mut writer = Writer::new(&mut data);
+
+        writer.write_header(&header).unwrap();
+        writer
+            .write_event(&Event::output(1, "ż".as_bytes()))
+            .unwrap();
+        writer.write_event(&Event::input(500_000, b"x")).unwrap();
+        writer
+            .write_event(&Event::bytes(1_230_000, b"ok\r\n"))
+            .unwrap();
+        writer
+            .write_event(&Event::marker(2_000_000, "end".to_owned()))
+            .unwrap();
+        writer.finish().unwrap();
+
+        let json: serde_json::Value = serde_json::from_slice(&data).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "version": 1,
+                "width": 80,
+                "height": 24,
+                "duration": 2.0,
+                "title": "Demo",
+                "env": {"SHELL": "/bin/zsh", "TERM": "xterm-256color"},
+                "stdout": [[0.000001, "ż"], [1.229999, "ok\r\n"]]
+            })
+        );
+
+        let asciicast::Asciicast { header, events, .. } =
+            super::load(String::from_utf8(data).unwrap()).unwrap();
+
+        let events: Vec<Event> = events.collect::<anyhow::Result<_>>().unwrap();
+
+        assert_eq!(header.term_type.as_deref(), Some("xterm-256color"));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].time, 1_230_000);
+        assert!(matches!(events[1].data, EventData::Output(ref s) if s == "ok\r\n"));
+    }
 }
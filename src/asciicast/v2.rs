@@ -1,9 +1,23 @@
-use super::{util, Asciicast, Event, EventData, Header};
+use super::{util, Asciicast, Event, EventData, Header, Position, Theme};
 use anyhow::{anyhow, bail, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
 use serde::{Deserialize, Deserializer};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::io::{self, Write};
 
+const FIELDS: [&str; 9] = [
+    "version",
+    "width",
+    "height",
+    "timestamp",
+    "idle_time_limit",
+    "command",
+    "title",
+    "env",
+    "theme",
+];
+
 #[derive(Deserialize)]
 struct V2Header {
     version: u8,
@@ -14,6 +28,10 @@ struct V2Header {
     command: Option<String>,
     title: Option<String>,
     env: Option<HashMap<String, String>>,
+    #[serde(default, deserialize_with = "util::deserialize_theme")]
+    theme: Option<Theme>,
+    #[serde(flatten)]
+    extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -29,9 +47,10 @@ struct V2Event {
 enum V2EventCode {
     Output,
     Input,
+    Bytes,
     Resize,
     Marker,
-    Other(char),
+    Other(String),
 }
 
 pub struct Parser(V2Header);
@@ -47,48 +66,64 @@ pub fn open(header_line: &str) -> Result<Parser> {
 }
 
 impl Parser {
-    pub fn parse<'a, I: Iterator<Item = io::Result<String>> + 'a>(
-        &self,
-        lines: I,
-    ) -> Asciicast<'a> {
+    pub fn parse<'a, I: Iterator<Item = util::Line> + 'a>(&self, lines: I) -> Asciicast<'a> {
         let header = Header {
             version: 2,
             cols: self.0.width,
             rows: self.0.height,
+            term_type: self.0.env.as_ref().and_then(|env| env.get("TERM")).cloned(),
+            term_version: None,
             timestamp: self.0.timestamp,
             idle_time_limit: self.0.idle_time_limit,
             command: self.0.command.clone(),
             title: self.0.title.clone(),
             env: self.0.env.clone(),
+            theme: self.0.theme.clone(),
+            extra: self.0.extra.clone(),
         };
 
+        let position = Position::default();
+        let last = position.clone();
+
+        let lines = lines.inspect(move |line| last.set(line));
         let events = Box::new(lines.filter_map(parse_line));
 
-        Asciicast { header, events }
+        Asciicast {
+            header,
+            events,
+            position,
+        }
     }
 }
 
-fn parse_line(line: io::Result<String>) -> Option<Result<Event>> {
-    match line {
-        Ok(line) => {
-            if line.is_empty() {
-                None
-            } else {
-                Some(parse_event(line))
-            }
-        }
+pub fn parse_line(mut line: util::Line) -> Option<Result<Event>> {
+    let text = match line.take_text() {
+        Ok(text) => text,
+        Err(e) => return Some(Err(e)),
+    };
 
-        Err(e) => Some(Err(e.into())),
+    if text.is_empty() {
+        None
+    } else {
+        Some(parse_event(&text).map_err(|e| line.parse_error(e)))
     }
 }
 
-fn parse_event(line: String) -> Result<Event> {
-    let event = serde_json::from_str::<V2Event>(&line)?;
+fn parse_event(line: &str) -> Result<Event> {
+    let event = serde_json::from_str::<V2Event>(line)?;
 
     let data = match event.code {
         V2EventCode::Output => EventData::Output(event.data),
         V2EventCode::Input => EventData::Input(event.data),
 
+        V2EventCode::Bytes => {
+            let data = BASE64
+                .decode(&event.data)
+                .map_err(|e| anyhow!("invalid base64 data in {} event: {e}", util::BYTES_CODE))?;
+
+            EventData::Bytes(data)
+        }
+
         V2EventCode::Resize => match event.data.split_once('x') {
             Some((cols, rows)) => {
                 let cols: u16 = cols
@@ -129,10 +164,11 @@ where
     match value {
         "o" => Ok(Output),
         "i" => Ok(Input),
+        util::BYTES_CODE => Ok(Bytes),
         "r" => Ok(Resize),
         "m" => Ok(Marker),
         "" => Err(Error::custom("missing event code")),
-        s => Ok(Other(s.chars().next().unwrap())),
+        s => Ok(Other(s.to_owned())),
     }
 }
 
@@ -152,40 +188,52 @@ where
         }
     }
 
+    /// Flushes written events and returns the underlying writer.
+    pub fn get_mut(&mut self) -> io::Result<&mut W> {
+        self.writer.flush()?;
+
+        Ok(self.writer.get_mut())
+    }
+
     pub fn write_header(&mut self, header: &Header) -> io::Result<()> {
         let header: V2Header = header.into();
 
         writeln!(self.writer, "{}", serde_json::to_string(&header)?)
     }
 
+    /// Writes the event, skipping exit events, which v2 can't represent.
     pub fn write_event(&mut self, event: &Event) -> io::Result<()> {
-        writeln!(self.writer, "{}", self.serialize_event(event)?)
+        match self.serialize_event(event)? {
+            Some(line) => writeln!(self.writer, "{line}"),
+            None => Ok(()),
+        }
     }
 
-    fn serialize_event(&self, event: &Event) -> Result<String, serde_json::Error> {
+    fn serialize_event(&self, event: &Event) -> Result<Option<String>, serde_json::Error> {
         use EventData::*;
 
         let (code, data) = match &event.data {
-            Output(data) => ('o', serde_json::to_string(data)?),
-            Input(data) => ('i', serde_json::to_string(data)?),
-            Resize(cols, rows) => ('r', serde_json::to_string(&format!("{cols}x{rows}"))?),
-            Marker(data) => ('m', serde_json::to_string(data)?),
-            Other(code, data) => (*code, serde_json::to_string(data)?),
+            Output(data) => ("o", serde_json::to_string(data)?),
+            Input(data) => ("i", serde_json::to_string(data)?),
+            Bytes(data) => (
+                util::BYTES_CODE,
+                serde_json::to_string(&BASE64.encode(data))?,
+            ),
+            Resize(cols, rows) => ("r", serde_json::to_string(&format!("{cols}x{rows}"))?),
+            Marker(data) => ("m", serde_json::to_string(data)?),
+            Exit(_) => return Ok(None),
+            Other(code, data) => (code.as_str(), serde_json::to_string(data)?),
         };
 
-        Ok(format!(
+        Ok(Some(format!(
             "[{}, {}, {}]",
-            format_time(event.time + self.time_offset).trim_end_matches('0'),
-            serde_json::to_string(&code)?,
+            util::format_time(event.time + self.time_offset),
+            serde_json::to_string(code)?,
             data,
-        ))
+        )))
     }
 }
 
-fn format_time(time: u64) -> String {
-    format!("{}.{:0>6}", time / 1_000_000, time % 1_000_000)
-}
-
 impl serde::Serialize for V2Header {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -215,6 +263,12 @@ impl serde::Serialize for V2Header {
             len += 1;
         }
 
+        if self.theme.is_some() {
+            len += 1;
+        }
+
+        len += self.extra.len();
+
         let mut map = serializer.serialize_map(Some(len))?;
         map.serialize_entry("version", &2)?;
         map.serialize_entry("width", &self.width)?;
@@ -238,10 +292,18 @@ impl serde::Serialize for V2Header {
 
         if let Some(env) = &self.env {
             if !env.is_empty() {
-                map.serialize_entry("env", &env)?;
+                map.serialize_entry("env", &env.iter().collect::<BTreeMap<_, _>>())?;
             }
         }
 
+        if let Some(theme) = &self.theme {
+            map.serialize_entry("theme", &theme)?;
+        }
+
+        for (key, value) in &self.extra {
+            map.serialize_entry(key, value)?;
+        }
+
         map.end()
     }
 }
@@ -256,7 +318,23 @@ impl From<&Header> for V2Header {
             idle_time_limit: header.idle_time_limit,
             command: header.command.clone(),
             title: header.title.clone(),
-            env: header.env.clone(),
+            env: build_env(header),
+            theme: header.theme.clone(),
+            extra: util::extra_fields(&header.extra, &FIELDS),
         }
     }
 }
+
+fn build_env(header: &Header) -> Option<HashMap<String, String>> {
+    match (&header.env, &header.term_type) {
+        (Some(env), Some(term_type)) if !env.contains_key("TERM") => {
+            let mut env = env.clone();
+            env.insert("TERM".to_owned(), term_type.clone());
+
+            Some(env)
+        }
+
+        (None, Some(term_type)) => Some(HashMap::from([("TERM".to_owned(), term_type.clone())])),
+        (env, _) => env.clone(),
+    }
+}
@@ -0,0 +1,447 @@
+use super::{util, Asciicast, Event, EventData, Header, Position, Theme};
+use anyhow::{anyhow, bail, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+const FIELDS: [&str; 7] = [
+    "version",
+    "term",
+    "timestamp",
+    "idle_time_limit",
+    "command",
+    "title",
+    "env",
+];
+
+#[derive(Deserialize, Serialize)]
+struct V3Header {
+    version: u8,
+    term: V3Term,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    idle_time_limit: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    command: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "is_empty_env")]
+    env: Option<BTreeMap<String, String>>,
+    #[serde(flatten)]
+    extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct V3Term {
+    cols: u16,
+    rows: u16,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    type_: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "util::deserialize_theme",
+        skip_serializing_if = "Option::is_none"
+    )]
+    theme: Option<Theme>,
+}
+
+#[derive(Debug, Deserialize)]
+struct V3Event {
+    #[serde(deserialize_with = "util::deserialize_time")]
+    interval: u64,
+    #[serde(deserialize_with = "deserialize_code")]
+    code: V3EventCode,
+    data: String,
+}
+
+#[derive(PartialEq, Debug)]
+enum V3EventCode {
+    Output,
+    Input,
+    Bytes,
+    Resize,
+    Marker,
+    Exit,
+    Other(String),
+}
+
+pub struct Parser(V3Header);
+
+pub fn open(header_line: &str) -> Result<Parser> {
+    let header = serde_json::from_str::<V3Header>(header_line)?;
+
+    if header.version != 3 {
+        bail!("unsupported asciicast version")
+    }
+
+    Ok(Parser(header))
+}
+
+impl Parser {
+    pub fn parse<'a, I: Iterator<Item = util::Line> + 'a>(&self, lines: I) -> Asciicast<'a> {
+        self.parse_from(lines, 0)
+    }
+
+    /// Parses events following an event at `base_time`, as event times are
+    /// stored as intervals.
+    pub fn parse_from<'a, I: Iterator<Item = util::Line> + 'a>(
+        &self,
+        lines: I,
+        base_time: u64,
+    ) -> Asciicast<'a> {
+        let header = Header {
+            version: 3,
+            cols: self.0.term.cols,
+            rows: self.0.term.rows,
+            term_type: self.0.term.type_.clone(),
+            term_version: self.0.term.version.clone(),
+            timestamp: self.0.timestamp,
+            idle_time_limit: self.0.idle_time_limit,
+            command: self.0.command.clone(),
+            title: self.0.title.clone(),
+            env: self.0.env.clone().map(|env| env.into_iter().collect()),
+            theme: self.0.term.theme.clone(),
+            extra: self.0.extra.clone(),
+        };
+
+        let mut prev_time = base_time;
+        let position = Position::default();
+        let last = position.clone();
+
+        let lines = lines.inspect(move |line| last.set(line));
+
+        let events = Box::new(lines.filter_map(parse_line).map(move |event| {
+            event.map(|event| {
+                prev_time += event.time;
+
+                Event {
+                    time: prev_time,
+                    ..event
+                }
+            })
+        }));
+
+        Asciicast {
+            header,
+            events,
+            position,
+        }
+    }
+}
+
+pub fn parse_line(mut line: util::Line) -> Option<Result<Event>> {
+    let text = match line.take_text() {
+        Ok(text) => text,
+        Err(e) => return Some(Err(e)),
+    };
+
+    if text.is_empty() || text.starts_with('#') {
+        None
+    } else {
+        Some(parse_event(&text).map_err(|e| line.parse_error(e)))
+    }
+}
+
+fn parse_event(line: &str) -> Result<Event> {
+    let event = serde_json::from_str::<V3Event>(line)?;
+
+    let data = match event.code {
+        V3EventCode::Output => EventData::Output(event.data),
+        V3EventCode::Input => EventData::Input(event.data),
+
+        V3EventCode::Bytes => {
+            let data = BASE64
+                .decode(&event.data)
+                .map_err(|e| anyhow!("invalid base64 data in {} event: {e}", util::BYTES_CODE))?;
+
+            EventData::Bytes(data)
+        }
+
+        V3EventCode::Resize => match event.data.split_once('x') {
+            Some((cols, rows)) => {
+                let cols: u16 = cols
+                    .parse()
+                    .map_err(|e| anyhow!("invalid cols value in resize event: {e}"))?;
+
+                let rows: u16 = rows
+                    .parse()
+                    .map_err(|e| anyhow!("invalid rows value in resize event: {e}"))?;
+
+                EventData::Resize(cols, rows)
+            }
+
+            None => {
+                bail!("invalid size value in resize event");
+            }
+        },
+
+        V3EventCode::Marker => EventData::Marker(event.data),
+
+        V3EventCode::Exit => {
+            let status: i32 = event
+                .data
+                .parse()
+                .map_err(|e| anyhow!("invalid status value in exit event: {e}"))?;
+
+            EventData::Exit(status)
+        }
+
+        V3EventCode::Other(c) => EventData::Other(c, event.data),
+    };
+
+    Ok(Event {
+        time: event.interval,
+        data,
+    })
+}
+
+fn deserialize_code<'de, D>(deserializer: D) -> Result<V3EventCode, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::Error;
+    use V3EventCode::*;
+
+    let value: &str = Deserialize::deserialize(deserializer)?;
+
+    match value {
+        "o" => Ok(Output),
+        "i" => Ok(Input),
+        util::BYTES_CODE => Ok(Bytes),
+        "r" => Ok(Resize),
+        "m" => Ok(Marker),
+        "x" => Ok(Exit),
+        "" => Err(Error::custom("missing event code")),
+        s => Ok(Other(s.to_owned())),
+    }
+}
+
+fn is_empty_env(env: &Option<BTreeMap<String, String>>) -> bool {
+    env.as_ref().is_none_or(|env| env.is_empty())
+}
+
+pub struct Writer<W: Write> {
+    writer: io::LineWriter<W>,
+    time_offset: u64,
+    prev_time: u64,
+}
+
+impl<W> Writer<W>
+where
+    W: Write,
+{
+    /// Creates a writer of events shifted by `time_offset`, which, when
+    /// appending to a recording, is the time of its last event.
+    pub fn new(writer: W, time_offset: u64) -> Self {
+        Self {
+            writer: io::LineWriter::new(writer),
+            time_offset,
+            prev_time: time_offset,
+        }
+    }
+
+    /// Flushes written events and returns the underlying writer.
+    pub fn get_mut(&mut self) -> io::Result<&mut W> {
+        self.writer.flush()?;
+
+        Ok(self.writer.get_mut())
+    }
+
+    pub fn write_header(&mut self, header: &Header) -> io::Result<()> {
+        let header: V3Header = header.into();
+
+        writeln!(self.writer, "{}", serde_json::to_string(&header)?)
+    }
+
+    /// Writes the event. Fails for an event earlier than the preceding one,
+    /// as intervals can't be negative (see `normalize_time`).
+    pub fn write_event(&mut self, event: &Event) -> io::Result<()> {
+        let time = event.time + self.time_offset;
+
+        let Some(interval) = time.checked_sub(self.prev_time) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "event at {}s is earlier than the preceding event at {}s",
+                    util::format_time(time),
+                    util::format_time(self.prev_time)
+                ),
+            ));
+        };
+
+        self.prev_time = time;
+
+        writeln!(self.writer, "{}", serialize_event(interval, event)?)
+    }
+}
+
+fn serialize_event(interval: u64, event: &Event) -> Result<String, serde_json::Error> {
+    use EventData::*;
+
+    let (code, data) = match &event.data {
+        Output(data) => ("o", serde_json::to_string(data)?),
+        Input(data) => ("i", serde_json::to_string(data)?),
+        Bytes(data) => (
+            util::BYTES_CODE,
+            serde_json::to_string(&BASE64.encode(data))?,
+        ),
+        Resize(cols, rows) => ("r", serde_json::to_string(&format!("{cols}x{rows}"))?),
+        Marker(data) => ("m", serde_json::to_string(data)?),
+        Exit(status) => ("x", serde_json::to_string(&status.to_string())?),
+        Other(code, data) => (code.as_str(), serde_json::to_string(data)?),
+    };
+
+    Ok(format!(
+        "[{}, {}, {}]",
+        util::format_time(interval),
+        serde_json::to_string(code)?,
+        data,
+    ))
+}
+
+impl From<&Header> for V3Header {
+    fn from(header: &Header) -> Self {
+        V3Header {
+            version: 3,
+            term: V3Term {
+                cols: header.cols,
+                rows: header.rows,
+                type_: header.term_type.clone(),
+                version: header.term_version.clone(),
+                theme: header.theme.clone(),
+            },
+            timestamp: header.timestamp,
+            idle_time_limit: header.idle_time_limit,
+            command: header.command.clone(),
+            title: header.title.clone(),
+            env: header.env.clone().map(|env| env.into_iter().collect()),
+            extra: util::extra_fields(&header.extra, &FIELDS),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Writer;
+    use crate::asciicast::{self, Event, EventData, Header};
+    use anyhow::Result;
+
+    #[test]
+    fn open_v3() {
+        let asciicast::Asciicast { header, events, .. } =
+            asciicast::open_from_path("tests/casts/minimal-v3.cast").unwrap();
+
+        let events = events.collect::<Result<Vec<Event>>>().unwrap();
+
+        assert_eq!(header.version, 3);
+        assert_eq!((header.cols, header.rows), (100, 50));
+        assert_eq!(header.term_type.as_deref(), Some("xterm-256color"));
+        assert_eq!(header.term_version.as_deref(), Some("VTE(7600)"));
+
+        assert_eq!(events[0].time, 1230000);
+        assert!(matches!(events[0].data, EventData::Output(ref s) if s == "hello"));
+
+        assert_eq!(events[1].time, 1330000);
+        assert!(matches!(events[1].data, EventData::Input(ref s) if s == "\r"));
+
+        assert_eq!(events[2].time, 2830000);
+        assert!(matches!(events[2].data, EventData::Resize(80, 24)));
+
+        assert_eq!(events[3].time, 2830000);
+        assert!(matches!(events[3].data, EventData::Marker(ref s) if s == "done"));
+
+        assert_eq!(events[4].time, 3030001);
+        assert!(matches!(events[4].data, EventData::Exit(1)));
+    }
+
+    #[test]
+    fn writer_v3() {
+        let mut data = Vec::new();
+
+        {
+            let mut fw = Writer::new(&mut data, 0);
+
+            let header = Header {
+                version: 3,
+                cols: 80,
+                rows: 24,
+                term_type: Some("xterm-256color".to_owned()),
+                term_version: None,
+                timestamp: Some(1704719152),
+                idle_time_limit: Some(1.5),
+                command: None,
+                title: Some("Demo".to_owned()),
+                env: Default::default(),
+                theme: None,
+                extra: Default::default(),
+            };
+
+            fw.write_header(&header).unwrap();
+
+            fw.write_event(&Event::output(1000001, "hello\r\n".as_bytes()))
+                .unwrap();
+
+            fw.write_event(&Event::input(1000001, " ".as_bytes()))
+                .unwrap();
+
+            fw.write_event(&Event::resize(3000003, (100, 40))).unwrap();
+            fw.write_event(&Event::marker(3500000, "x".to_owned()))
+                .unwrap();
+            fw.write_event(&Event::exit(4000000, 0)).unwrap();
+        }
+
+        let lines = parse(data);
+
+        assert_eq!(lines[0]["version"], 3);
+        assert_eq!(lines[0]["term"]["cols"], 80);
+        assert_eq!(lines[0]["term"]["rows"], 24);
+        assert_eq!(lines[0]["term"]["type"], "xterm-256color");
+        assert!(lines[0]["term"]["version"].is_null());
+        assert_eq!(lines[0]["timestamp"], 1704719152);
+        assert_eq!(lines[0]["idle_time_limit"], 1.5);
+        assert_eq!(lines[0]["title"], "Demo");
+        assert!(lines[0]["env"].is_null());
+        assert_eq!(lines[1][0], 1.000001);
+        assert_eq!(lines[1][1], "o");
+        assert_eq!(lines[1][2], "hello\r\n");
+        assert_eq!(lines[2][0], 0.0);
+        assert_eq!(lines[2][1], "i");
+        assert_eq!(lines[2][2], " ");
+        assert_eq!(lines[3][0], 2.000002);
+        assert_eq!(lines[3][1], "r");
+        assert_eq!(lines[3][2], "100x40");
+        assert_eq!(lines[4][0], 0.499997);
+        assert_eq!(lines[4][1], "m");
+        assert_eq!(lines[4][2], "x");
+        assert_eq!(lines[5][0], 0.5);
+        assert_eq!(lines[5][1], "x");
+        assert_eq!(lines[5][2], "0");
+    }
+
+    #[test]
+    fn writer_v3_going_back() {
+        let mut data = Vec::new();
+        let mut fw = Writer::new(&mut data, 0);
+
+        fw.write_event(&Event::output(2000000, b"foo")).unwrap();
+        let result = fw.write_event(&Event::output(1000000, b"bar"));
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "event at 1.0s is earlier than the preceding event at 2.0s"
+        );
+    }
+
+    fn parse(json: Vec<u8>) -> Vec<serde_json::Value> {
+        String::from_utf8(json)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+}
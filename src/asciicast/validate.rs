@@ -0,0 +1,204 @@
+use super::{util, v1, v2, v3, EventData, ParseError};
+use std::io::BufRead;
+
+enum Format {
+    V1,
+    V2,
+    V3,
+}
+
+/// Problems found in a recording by `validate`.
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    /// Problems making (parts of) the recording unreadable.
+    pub errors: Vec<ParseError>,
+    /// Problems players can get past, e.g. event codes they don't know.
+    pub warnings: Vec<ParseError>,
+}
+
+impl ValidationReport {
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty() && self.warnings.is_empty()
+    }
+}
+
+impl From<Vec<ParseError>> for ValidationReport {
+    fn from(errors: Vec<ParseError>) -> Self {
+        ValidationReport {
+            errors,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+/// Checks a recording for problems. Unlike `open`, which stops at the
+/// first error, it reports all of them.
+pub fn validate<R: BufRead>(reader: R) -> ValidationReport {
+    let mut lines = util::Lines::new(reader);
+
+    let Some(mut first_line) = lines.next() else {
+        return vec![problem(1, None, 0, "empty file")].into();
+    };
+
+    let text = match first_line.take_text() {
+        Ok(text) => text,
+        Err(e) => return vec![into_parse_error(e)].into(),
+    };
+
+    let format = match serde_json::from_str::<serde_json::Value>(&text) {
+        Ok(value) => match value.get("version").and_then(|v| v.as_u64()) {
+            Some(1) => Format::V1,
+            Some(2) => Format::V2,
+            Some(3) => Format::V3,
+            Some(v) => return vec![problem(1, None, 0, format!("unsupported version {v}"))].into(),
+            None => return vec![problem(1, None, 0, "missing or invalid version field")].into(),
+        },
+
+        // v1 is a JSON document that may span multiple lines
+        Err(_) => Format::V1,
+    };
+
+    match format {
+        Format::V1 => validate_v1(text, lines).into(),
+
+        Format::V2 => match v2::open(&text) {
+            Ok(_) => validate_events(format, lines),
+            Err(e) => vec![into_parse_error(first_line.parse_error(e))].into(),
+        },
+
+        Format::V3 => match v3::open(&text) {
+            Ok(_) => validate_events(format, lines),
+            Err(e) => vec![into_parse_error(first_line.parse_error(e))].into(),
+        },
+    }
+}
+
+fn validate_v1<R: BufRead>(first_line: String, lines: util::Lines<R>) -> Vec<ParseError> {
+    let mut json = vec![first_line];
+
+    for mut line in lines {
+        match line.take_text() {
+            Ok(text) => json.push(text),
+            Err(e) => return vec![into_parse_error(e)],
+        }
+    }
+
+    v1::validate(&json.join("\n"))
+}
+
+fn validate_events<R: BufRead>(format: Format, lines: util::Lines<R>) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    let mut prev_time = 0;
+
+    for line in lines {
+        let (number, offset) = (line.number, line.offset);
+        let text = line.text.as_ref().ok().cloned().unwrap_or_default();
+
+        let time_column = text
+            .find(|c: char| c != '[' && !c.is_whitespace())
+            .map(|i| i + 1);
+
+        let event = match format {
+            Format::V3 => v3::parse_line(line),
+            _ => v2::parse_line(line),
+        };
+
+        match event {
+            Some(Ok(event)) => {
+                let time = match format {
+                    Format::V3 => prev_time + event.time,
+                    _ => event.time,
+                };
+
+                if time < prev_time {
+                    report.errors.push(problem(
+                        number,
+                        time_column,
+                        offset,
+                        format!(
+                            "timestamp {} is earlier than previous event's timestamp {}",
+                            util::format_time(time),
+                            util::format_time(prev_time)
+                        ),
+                    ));
+                }
+
+                if let EventData::Other(code, _) = &event.data {
+                    let code_column = serde_json::to_string(code)
+                        .ok()
+                        .and_then(|quoted| text.find(&quoted))
+                        .map(|i| i + 1);
+
+                    report.warnings.push(problem(
+                        number,
+                        code_column,
+                        offset,
+                        format!("unknown event code {code:?}"),
+                    ));
+                }
+
+                prev_time = time;
+            }
+
+            Some(Err(e)) => report.errors.push(
+                e.downcast::<ParseError>()
+                    .unwrap_or_else(|e| problem(number, None, offset, e)),
+            ),
+
+            None => (),
+        }
+    }
+
+    report
+}
+
+fn problem<S: ToString>(line: usize, column: Option<usize>, offset: u64, message: S) -> ParseError {
+    ParseError {
+        line,
+        column,
+        offset,
+        message: message.to_string(),
+    }
+}
+
+fn into_parse_error(e: anyhow::Error) -> ParseError {
+    e.downcast::<ParseError>()
+        .unwrap_or_else(|e| problem(1, None, 0, e))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn validate() {
+        let data = b"{\"version\": 2, \"width\": 80, \"height\": 24}\n[1.0, \"o\", \"a\"]\n[0.5, \"o\", \"b\"]\n[2.0, \"r\", \"80\"]\n[3.0, \"o\" \"c\"]\n[4.0, \"o\", \"\xffx\"]\n[5.0, \"z\", \"d\"]\n";
+
+        let report = super::validate(&data[..]);
+        let errors: Vec<_> = report.errors.iter().map(|e| (e.line, e.column)).collect();
+
+        assert_eq!(
+            errors,
+            vec![(3, Some(2)), (4, None), (5, Some(11)), (6, Some(13))]
+        );
+
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(
+            (report.warnings[0].line, report.warnings[0].column),
+            (7, Some(7))
+        );
+        assert_eq!(report.warnings[0].message, "unknown event code \"z\"");
+
+        let data = "{\n  \"version\": 1,\n  \"width\": 80,\n  \"height\": 24,\n  \"stdout\": [[0.1],\n    [0.2, \"ok\"],\n    [\"x\", \"y\"]]\n}";
+        let errors: Vec<_> = super::validate(data.as_bytes())
+            .errors
+            .iter()
+            .map(|e| (e.line, e.column))
+            .collect();
+
+        assert_eq!(errors, vec![(5, Some(18)), (7, Some(10))]);
+
+        let data =
+            "{\"version\": 3, \"term\": {\"cols\": 80, \"rows\": 24}}\n[0.5, \"o\", \"a\"]\n";
+
+        assert!(super::validate(data.as_bytes()).is_empty());
+    }
+}
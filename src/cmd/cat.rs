@@ -1,39 +1,103 @@
+use super::OutOfOrder;
 use crate::asciicast;
+use crate::encoder::{self, Encoder};
+use crate::tty;
+use crate::util;
 use anyhow::Result;
-use clap::Args;
+use clap::{Args, ValueEnum};
 use std::io;
 
 #[derive(Debug, Args)]
 pub struct Cli {
     #[arg(required = true)]
     filename: Vec<String>,
+
+    /// Output file format [default: format of the first recording]
+    #[arg(short, long, value_enum)]
+    format: Option<Format>,
+
+    /// Override terminal size of the recordings (e.g. for ttyrec files)
+    #[arg(long, value_parser = util::parse_tty_size, value_name = "COLSxROWS")]
+    tty_size: Option<util::TtySize>,
+
+    /// How to handle events going back in time
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t)]
+    out_of_order: OutOfOrder,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Format {
+    Asciicast,
+    AsciicastV3,
 }
 
 impl Cli {
     pub fn run(self) -> Result<()> {
-        let mut writer = asciicast::Writer::new(io::stdout(), 0);
+        let mut encoder: Option<encoder::AsciicastEncoder<io::Stdout>> = None;
         let mut time_offset: u64 = 0;
-        let mut first = true;
 
-        for path in self.filename.iter() {
-            let recording = asciicast::open_from_path(path)?;
-            let mut time = time_offset;
+        for (i, path) in self.filename.iter().enumerate() {
+            let last = i == self.filename.len() - 1;
+            let mut recording = asciicast::open_from_path(path)?;
 
-            if first {
-                writer.write_header(&recording.header)?;
-                first = false;
+            if let Some(util::TtySize((cols, rows))) = self.tty_size {
+                recording.header.cols = cols.unwrap_or(recording.header.cols);
+                recording.header.rows = rows.unwrap_or(recording.header.rows);
             }
 
-            for event in recording.events {
+            let mut time = time_offset;
+
+            let encoder = match &mut encoder {
+                Some(encoder) => encoder,
+
+                None => {
+                    let header = &recording.header;
+                    let version = self.get_version(header);
+                    let tty_size = tty::TtySize(header.cols, header.rows);
+
+                    let mut e = encoder::AsciicastEncoder::new(
+                        io::stdout(),
+                        version,
+                        false,
+                        0,
+                        header.into(),
+                    );
+
+                    e.start(header.timestamp, &tty_size)?;
+
+                    encoder.insert(e)
+                }
+            };
+
+            for event in asciicast::normalize_time(recording, self.out_of_order.into()).events {
                 let mut event = event?;
                 time = time_offset + event.time;
                 event.time = time;
-                writer.write_event(&event)?;
+
+                // only the last recording's exit marks the end of the session
+                if !last && matches!(event.data, asciicast::EventData::Exit(_)) {
+                    continue;
+                }
+
+                encoder.event(&event)?;
             }
 
             time_offset = time;
         }
 
+        if let Some(mut encoder) = encoder {
+            encoder.finish()?;
+        }
+
         Ok(())
     }
+
+    fn get_version(&self, header: &asciicast::Header) -> asciicast::Version {
+        match self.format {
+            Some(Format::Asciicast) => asciicast::Version::V2,
+            Some(Format::AsciicastV3) => asciicast::Version::V3,
+            None if header.version == 3 => asciicast::Version::V3,
+            None => asciicast::Version::V2,
+        }
+    }
 }
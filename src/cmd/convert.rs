@@ -1,5 +1,8 @@
+use super::OutOfOrder;
 use crate::asciicast::{self, Header};
-use crate::encoder;
+use crate::compress::{self, Compression};
+use crate::encoder::{self, Finish};
+use crate::logger;
 use crate::util;
 use anyhow::{bail, Result};
 use clap::{Args, ValueEnum};
@@ -17,69 +20,349 @@ pub struct Cli {
     #[arg(short, long, value_enum)]
     format: Option<Format>,
 
+    /// Start output at a given time
+    #[arg(long, value_name = "SECS")]
+    from: Option<f64>,
+
+    /// End output at a given time
+    #[arg(long, value_name = "SECS")]
+    to: Option<f64>,
+
+    /// Take a snapshot at a given time (with html-snapshot and png formats)
+    #[arg(long, value_name = "SECS", conflicts_with_all = ["to", "at_marker"])]
+    at: Option<f64>,
+
+    /// Take a snapshot at the Nth marker (with html-snapshot and png formats)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "to")]
+    at_marker: Option<u32>,
+
+    /// Limit idle time to a given number of seconds (with gif, svg, html,
+    /// vtt, srt and chapters formats) [default: from the recording]
+    #[arg(short, long, value_name = "SECS")]
+    idle_time_limit: Option<f64>,
+
+    /// Set playback speed (with vtt, srt and chapters formats) [default: 1]
+    #[arg(short, long)]
+    speed: Option<f64>,
+
+    /// script(1) timing file, for replaying a typescript given as input
+    #[arg(long, value_name = "FILE")]
+    timing: Option<String>,
+
+    /// script(1) input log, for typescripts recorded with --log-in
+    #[arg(long, value_name = "FILE", requires = "timing")]
+    log_in: Option<String>,
+
+    /// Override terminal size of the recording (e.g. for ttyrec files)
+    #[arg(long, value_parser = util::parse_tty_size, value_name = "COLSxROWS")]
+    tty_size: Option<util::TtySize>,
+
+    /// How to handle events going back in time
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t)]
+    out_of_order: OutOfOrder,
+
+    /// When to take screen snapshots with frames format: change, marker or
+    /// interval in seconds
+    #[arg(long, value_parser = parse_snapshot_every, value_name = "WHEN", default_value = "change")]
+    snapshot_every: encoder::SnapshotEvery,
+
     /// Overwrite target file if it already exists
     #[arg(long)]
     overwrite: bool,
 }
 
-#[derive(Clone, Copy, Debug, ValueEnum)]
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
 enum Format {
     Asciicast,
+    AsciicastV3,
+    AsciicastV1,
     Raw,
     Txt,
+    Ansi,
+    Markdown,
+    Ttyrec,
+    Script,
+    ScriptAdvanced,
+    Gif,
+    Svg,
+    Html,
+    HtmlSnapshot,
+    Png,
+    Frames,
+    Vtt,
+    Srt,
+    Chapters,
+    Keystrokes,
 }
 
 use crate::encoder::EncoderExt;
 
 impl Cli {
     pub fn run(self) -> Result<()> {
+        self.check_options()?;
+
         let path = util::get_local_path(&self.input_filename)?;
-        let input = asciicast::open_from_path(&*path)?;
-        let mut output = self.get_output(&input.header)?;
+        let from = self.from.map_or(0, |secs| (secs * 1_000_000.0) as u64);
+
+        let mut input = match (&self.timing, self.from) {
+            (Some(timing), _) => asciicast::seek(
+                asciicast::script::open_from_paths(timing, &*path, self.log_in.as_ref())?,
+                from,
+            ),
+
+            (None, Some(_)) => asciicast::open_from_path_at(&*path, from)?,
+            (None, None) => asciicast::open_from_path(&*path)?,
+        };
+
+        if let Some(util::TtySize((cols, rows))) = self.tty_size {
+            input.header.cols = cols.unwrap_or(input.header.cols);
+            input.header.rows = rows.unwrap_or(input.header.rows);
+        }
+
+        input = asciicast::normalize_time(input, self.out_of_order.into());
+
+        if let Some(to) = self.to.or(self.at) {
+            let end = ((to * 1_000_000.0) as u64).saturating_sub(from);
+
+            input.events = Box::new(
+                input
+                    .events
+                    .take_while(move |e| e.as_ref().map_or(true, |e| e.time <= end)),
+            );
+        }
+
+        if let Some(n) = self.at_marker {
+            input.events = Box::new(asciicast::until_marker(input.events, n as usize));
+        }
+
+        let format = self.get_format(&input.header);
+
+        if matches!(
+            format,
+            Format::Gif | Format::Svg | Format::Html | Format::Vtt | Format::Srt | Format::Chapters
+        ) {
+            if let Some(limit) = self.idle_time_limit.or(input.header.idle_time_limit) {
+                input.events = Box::new(asciicast::limit_idle_time(input.events, limit));
+            }
+        }
+
+        if matches!(format, Format::Vtt | Format::Srt | Format::Chapters) {
+            input.events = Box::new(asciicast::accelerate(
+                input.events,
+                self.speed.unwrap_or(1.0),
+            ));
+        }
 
-        output.encode(input)
+        let mut output = self.get_output(format, &input.header)?;
+        output.encode(input)?;
+
+        Ok(())
+    }
+
+    /// Checks that format specific options are used with a format they
+    /// apply to. It's done after parsing, as the format may come from the
+    /// output filename.
+    fn check_options(&self) -> Result<()> {
+        use Format::*;
+
+        let format = self.output_format();
+
+        let options: [(&str, bool, &[Format]); 4] = [
+            ("--at", self.at.is_some(), &[HtmlSnapshot, Png]),
+            (
+                "--at-marker",
+                self.at_marker.is_some(),
+                &[HtmlSnapshot, Png],
+            ),
+            (
+                "--idle-time-limit",
+                self.idle_time_limit.is_some(),
+                &[Gif, Svg, Html, Vtt, Srt, Chapters],
+            ),
+            ("--speed", self.speed.is_some(), &[Vtt, Srt, Chapters]),
+        ];
+
+        for (name, used, formats) in options {
+            if used && !format.is_some_and(|f| formats.contains(&f)) {
+                let formats: Vec<_> = formats
+                    .iter()
+                    .filter_map(|f| f.to_possible_value())
+                    .map(|v| v.get_name().to_owned())
+                    .collect();
+
+                bail!(
+                    "the argument '{name}' can only be used with {} formats",
+                    formats.join(", ")
+                );
+            }
+        }
+
+        Ok(())
     }
 
-    fn get_output(&self, header: &Header) -> Result<Box<dyn encoder::Encoder>> {
-        let file = self.open_file()?;
+    fn get_format(&self, header: &Header) -> Format {
+        self.output_format().unwrap_or(if header.version == 3 {
+            Format::AsciicastV3
+        } else {
+            Format::Asciicast
+        })
+    }
 
-        let format = self.format.unwrap_or_else(|| {
-            if self.output_filename.to_lowercase().ends_with(".txt") {
-                Format::Txt
+    /// Returns the format given with `--format` or implied by the output
+    /// filename extension, if any.
+    fn output_format(&self) -> Option<Format> {
+        self.format.or_else(|| {
+            let filename = compress::strip_extension(&self.output_filename).to_lowercase();
+
+            if filename.ends_with(".txt") {
+                Some(Format::Txt)
+            } else if filename.ends_with(".md") {
+                Some(Format::Markdown)
+            } else if filename.ends_with(".ttyrec") {
+                Some(Format::Ttyrec)
+            } else if filename.ends_with(".gif") {
+                Some(Format::Gif)
+            } else if filename.ends_with(".svg") {
+                Some(Format::Svg)
+            } else if filename.ends_with(".html") {
+                Some(Format::Html)
+            } else if filename.ends_with(".png") {
+                Some(Format::Png)
+            } else if filename.ends_with(".vtt") {
+                Some(Format::Vtt)
+            } else if filename.ends_with(".srt") {
+                Some(Format::Srt)
             } else {
-                Format::Asciicast
+                None
             }
-        });
+        })
+    }
+
+    fn get_output(&self, format: Format, header: &Header) -> Result<Box<dyn encoder::Encoder>> {
+        let file = self.open_file(&self.output_filename)?;
 
         match format {
             Format::Asciicast => Ok(Box::new(encoder::AsciicastEncoder::new(
                 file,
+                asciicast::Version::V2,
                 false,
                 0,
                 header.into(),
             ))),
 
-            Format::Raw => Ok(Box::new(encoder::RawEncoder::new(file, false))),
+            Format::AsciicastV3 => Ok(Box::new(encoder::AsciicastEncoder::new(
+                file,
+                asciicast::Version::V3,
+                false,
+                0,
+                header.into(),
+            ))),
+
+            Format::AsciicastV1 => Ok(Box::new(encoder::AsciicastEncoder::new(
+                file,
+                asciicast::Version::V1,
+                false,
+                0,
+                header.into(),
+            ))),
+
+            Format::Raw => Ok(Box::new(encoder::RawEncoder::new(
+                file,
+                false,
+                header.theme.clone(),
+            ))),
             Format::Txt => Ok(Box::new(encoder::TextEncoder::new(file))),
+            Format::Ansi => Ok(Box::new(encoder::AnsiEncoder::new(file))),
+
+            Format::Markdown => Ok(Box::new(encoder::MarkdownEncoder::new(
+                file,
+                header.title.clone(),
+            ))),
+
+            Format::Ttyrec => Ok(Box::new(encoder::TtyrecEncoder::new(file, false))),
+
+            Format::Script | Format::ScriptAdvanced => {
+                let timing_filename = format!("{}.timing", self.output_filename);
+                let timing = self.open_file(&timing_filename)?;
+                let advanced = matches!(format, Format::ScriptAdvanced);
+
+                logger::info!("Writing timing to {}", timing_filename);
+
+                Ok(Box::new(encoder::ScriptEncoder::new(
+                    file, timing, advanced,
+                )))
+            }
+
+            Format::Gif => Ok(Box::new(encoder::GifEncoder::new(
+                file,
+                header.theme.clone(),
+            ))),
+
+            Format::Svg => Ok(Box::new(encoder::SvgEncoder::new(
+                file,
+                header.theme.clone(),
+            ))),
+
+            Format::Html => Ok(Box::new(encoder::HtmlEncoder::new(
+                file,
+                header.theme.clone(),
+                header.title.clone(),
+            ))),
+
+            Format::HtmlSnapshot => Ok(Box::new(encoder::HtmlSnapshotEncoder::new(
+                file,
+                header.theme.clone(),
+                header.title.clone(),
+            ))),
+
+            Format::Png => Ok(Box::new(encoder::PngEncoder::new(
+                file,
+                header.theme.clone(),
+            ))),
+
+            Format::Frames => Ok(Box::new(encoder::FramesEncoder::new(
+                file,
+                self.snapshot_every,
+            ))),
+
+            Format::Vtt => Ok(Box::new(encoder::MarkersEncoder::new(
+                file,
+                encoder::MarkersFormat::WebVtt,
+            ))),
+
+            Format::Srt => Ok(Box::new(encoder::MarkersEncoder::new(
+                file,
+                encoder::MarkersFormat::Srt,
+            ))),
+
+            Format::Chapters => Ok(Box::new(encoder::MarkersEncoder::new(
+                file,
+                encoder::MarkersFormat::Chapters,
+            ))),
+
+            Format::Keystrokes => Ok(Box::new(encoder::KeystrokesEncoder::new(file))),
         }
     }
 
-    fn open_file(&self) -> Result<fs::File> {
-        let overwrite = self.get_mode()?;
+    fn open_file(&self, filename: &str) -> Result<Box<dyn Finish + Send>> {
+        let overwrite = self.get_mode(filename)?;
 
         let file = fs::OpenOptions::new()
             .write(true)
             .create(overwrite)
             .create_new(!overwrite)
             .truncate(overwrite)
-            .open(&self.output_filename)?;
+            .open(filename)?;
 
-        Ok(file)
+        match Compression::from_filename(filename) {
+            Some(compression) => Ok(Box::new(compress::encoder(file, compression)?)),
+            None => Ok(Box::new(file)),
+        }
     }
 
-    fn get_mode(&self) -> Result<bool> {
+    fn get_mode(&self, filename: &str) -> Result<bool> {
         let mut overwrite = self.overwrite;
-        let path = Path::new(&self.output_filename);
+        let path = Path::new(filename);
 
         if path.exists() {
             let metadata = fs::metadata(path)?;
@@ -96,3 +379,55 @@ impl Cli {
         Ok(overwrite)
     }
 }
+
+fn parse_snapshot_every(s: &str) -> Result<encoder::SnapshotEvery> {
+    match s {
+        "change" => Ok(encoder::SnapshotEvery::Change),
+        "marker" => Ok(encoder::SnapshotEvery::Marker),
+
+        _ => match s.parse::<f64>() {
+            Ok(secs) if secs > 0.0 => Ok(encoder::SnapshotEvery::Interval(
+                (secs * 1_000_000.0) as u64,
+            )),
+
+            _ => bail!("expected change, marker or a positive number of seconds"),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cli;
+    use anyhow::Result;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Command {
+        #[command(flatten)]
+        cli: Cli,
+    }
+
+    fn check(args: &[&str]) -> Result<()> {
+        let args = ["convert", "demo.cast"].iter().chain(args);
+        let Command { cli } = Command::try_parse_from(args)?;
+
+        cli.check_options()
+    }
+
+    #[test]
+    fn format_specific_options() {
+        assert!(check(&["demo.png", "--at", "1"]).is_ok());
+        assert!(check(&["demo.html", "-f", "html-snapshot", "--at-marker", "2"]).is_ok());
+        assert!(check(&["demo.gif", "-i", "2"]).is_ok());
+        assert!(check(&["demo.vtt", "-i", "2", "-s", "2"]).is_ok());
+        assert!(check(&["demo.txt", "-f", "chapters", "-s", "2"]).is_ok());
+
+        assert!(check(&["demo.cast", "--at", "1"]).is_err());
+        assert!(check(&["demo.png", "-f", "gif", "--at", "1"]).is_err());
+        assert!(check(&["demo.html", "--at-marker", "1"]).is_err());
+        assert!(check(&["demo.txt", "-i", "2"]).is_err());
+        assert!(check(&["demo.gif", "-s", "2"]).is_err());
+        assert!(check(&["demo.png", "--at", "1", "--to", "2"]).is_err());
+        assert!(check(&["demo.png", "--at-marker", "0"]).is_err());
+    }
+}
@@ -0,0 +1,25 @@
+use crate::asciicast::Index;
+use crate::logger;
+use anyhow::Result;
+use clap::Args;
+
+#[derive(Debug, Args)]
+pub struct Cli {
+    filename: String,
+}
+
+impl Cli {
+    pub fn run(self) -> Result<()> {
+        let index = Index::build(&self.filename)?;
+        let path = index.save(&self.filename)?;
+
+        logger::info!(
+            "Indexed {} with {} checkpoint(s), index saved to {}",
+            self.filename,
+            index.checkpoint_count(),
+            path.display()
+        );
+
+        Ok(())
+    }
+}
@@ -1,6 +1,95 @@
 pub mod auth;
 pub mod cat;
 pub mod convert;
+pub mod index;
 pub mod play;
 pub mod rec;
+pub mod repair;
 pub mod upload;
+pub mod validate;
+use crate::asciicast::TimePolicy;
+use crate::config::Config;
+use anyhow::Result;
+use clap::{Parser, Subcommand, ValueEnum};
+
+#[derive(Debug, Parser)]
+#[clap(author, version, about)]
+#[command(name = "asciinema")]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+
+    /// asciinema server URL
+    #[arg(long)]
+    server_url: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Commands {
+    /// Record a terminal session
+    Rec(rec::Cli),
+
+    /// Replay a terminal session
+    Play(play::Cli),
+
+    /// Concatenate multiple recordings
+    Cat(cat::Cli),
+
+    /// Convert a recording into another format
+    Convert(convert::Cli),
+
+    /// Repair a truncated or damaged recording
+    Repair(repair::Cli),
+
+    /// Check recordings for errors
+    Validate(validate::Cli),
+
+    /// Build a time index for fast seeking in a recording
+    Index(index::Cli),
+
+    /// Upload a recording to an asciinema server
+    Upload(upload::Cli),
+
+    /// Authenticate this CLI with an asciinema server account
+    Auth(auth::Cli),
+}
+
+/// Runs the `asciinema` CLI.
+pub fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let config = Config::new(cli.server_url.clone())?;
+
+    match cli.command {
+        Commands::Rec(record) => record.run(&config),
+        Commands::Play(play) => play.run(&config),
+        Commands::Cat(cat) => cat.run(),
+        Commands::Convert(convert) => convert.run(),
+        Commands::Repair(repair) => repair.run(),
+        Commands::Validate(validate) => validate.run(),
+        Commands::Index(index) => index.run(),
+        Commands::Upload(upload) => upload.run(&config),
+        Commands::Auth(auth) => auth.run(&config),
+    }
+}
+
+/// `--out-of-order` values, mapped onto [`TimePolicy`].
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum OutOfOrder {
+    /// Move them to the preceding event's time
+    #[default]
+    Clamp,
+    /// Move them to their place in time order
+    Reorder,
+    /// Stop with an error
+    Error,
+}
+
+impl From<OutOfOrder> for TimePolicy {
+    fn from(value: OutOfOrder) -> Self {
+        match value {
+            OutOfOrder::Clamp => TimePolicy::Clamp,
+            OutOfOrder::Reorder => TimePolicy::Reorder,
+            OutOfOrder::Error => TimePolicy::Error,
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use crate::asciicast;
+use super::OutOfOrder;
+use crate::asciicast::{self, Asciicast};
 use crate::config::Config;
 use crate::logger;
 use crate::player::{self, KeyBindings};
@@ -6,6 +7,8 @@ use crate::tty;
 use crate::util;
 use anyhow::Result;
 use clap::Args;
+use std::cell::Cell;
+use std::rc::Rc;
 
 #[derive(Debug, Args)]
 pub struct Cli {
@@ -24,6 +27,26 @@ pub struct Cli {
     #[arg(short, long, name = "loop")]
     loop_: bool,
 
+    /// Start playback at a given time
+    #[arg(long, value_name = "SECS")]
+    from: Option<f64>,
+
+    /// script(1) timing file, for replaying a typescript given as input
+    #[arg(long, value_name = "FILE")]
+    timing: Option<String>,
+
+    /// script(1) input log, for typescripts recorded with --log-in
+    #[arg(long, value_name = "FILE", requires = "timing")]
+    log_in: Option<String>,
+
+    /// Override terminal size of the recording (e.g. for ttyrec files)
+    #[arg(long, value_parser = util::parse_tty_size, value_name = "COLSxROWS")]
+    tty_size: Option<util::TtySize>,
+
+    /// How to handle events going back in time
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t)]
+    out_of_order: OutOfOrder,
+
     /// Automatically pause on markers
     #[arg(short = 'm', long)]
     pause_on_markers: bool,
@@ -37,9 +60,49 @@ impl Cli {
         logger::info!("Replaying session from {}", self.filename);
 
         let path = util::get_local_path(&self.filename)?;
+        let damaged = Rc::new(Cell::new(0));
 
         let ended = loop {
-            let recording = asciicast::open_from_path(&*path)?;
+            // each --loop pass reads the recording anew
+            damaged.set(0);
+            let counter = damaged.clone();
+
+            let from = self.from.map_or(0, |secs| (secs * 1_000_000.0) as u64);
+
+            let Asciicast {
+                mut header,
+                events,
+                position,
+            } = match (&self.timing, self.from) {
+                (Some(timing), _) => asciicast::seek(
+                    asciicast::script::open_from_paths(timing, &*path, self.log_in.as_ref())?,
+                    from,
+                ),
+
+                (None, Some(_)) => asciicast::open_from_path_at(&*path, from)?,
+                (None, None) => asciicast::open_from_path(&*path)?,
+            };
+
+            if let Some(util::TtySize((cols, rows))) = self.tty_size {
+                header.cols = cols.unwrap_or(header.cols);
+                header.rows = rows.unwrap_or(header.rows);
+            }
+
+            let events = asciicast::skip_damaged(events, move |_| {
+                counter.set(counter.get() + 1);
+            });
+
+            let events = Box::new(events);
+
+            let recording = asciicast::normalize_time(
+                Asciicast {
+                    header,
+                    events,
+                    position,
+                },
+                self.out_of_order.into(),
+            );
+
             let tty = tty::DevTty::open()?;
             let keys = get_key_bindings(config)?;
 
@@ -63,6 +126,12 @@ impl Cli {
             logger::info!("Playback interrupted");
         }
 
+        if damaged.get() > 0 {
+            logger::info!(
+                "Skipped damaged data in the recording, use `asciinema repair` to fix the file"
+            );
+        }
+
         Ok(())
     }
 }
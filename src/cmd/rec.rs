@@ -1,12 +1,14 @@
 use crate::asciicast;
+use crate::compress::{self, Compression};
 use crate::config::Config;
-use crate::encoder;
+use crate::encoder::{self, Finish};
 use crate::locale;
 use crate::logger;
 use crate::notifier;
 use crate::pty;
 use crate::recorder::{self, KeyBindings};
 use crate::tty;
+use crate::util;
 use anyhow::{bail, Result};
 use clap::{Args, ValueEnum};
 use std::collections::{HashMap, HashSet};
@@ -22,6 +24,10 @@ pub struct Cli {
     #[arg(long, short = 'I', alias = "stdin")]
     input: bool,
 
+    /// Record output which isn't valid UTF-8 losslessly, as base64 encoded bytes
+    #[arg(long)]
+    lossless: bool,
+
     /// Append to an existing recording file
     #[arg(short, long)]
     append: bool,
@@ -54,8 +60,8 @@ pub struct Cli {
     idle_time_limit: Option<f64>,
 
     /// Override terminal size for the recorded command
-    #[arg(long, value_parser = parse_tty_size, value_name = "COLSxROWS")]
-    tty_size: Option<TtySize>,
+    #[arg(long, value_parser = util::parse_tty_size, value_name = "COLSxROWS")]
+    tty_size: Option<util::TtySize>,
 
     #[arg(long, hide = true)]
     cols: Option<u16>,
@@ -67,13 +73,12 @@ pub struct Cli {
 #[derive(Clone, Copy, Debug, ValueEnum)]
 enum Format {
     Asciicast,
+    AsciicastV3,
     Raw,
     Txt,
+    Ttyrec,
 }
 
-#[derive(Clone, Debug)]
-struct TtySize((Option<u16>, Option<u16>));
-
 impl Cli {
     pub fn run(self, config: &Config) -> Result<()> {
         locale::check_utf8_locale()?;
@@ -103,15 +108,18 @@ impl Cli {
                 Box::new(tty::NullTty::open()?)
             };
 
-            let mut recorder = recorder::Recorder::new(output, record_input, keys, notifier);
+            let mut recorder =
+                recorder::Recorder::new(output, record_input, self.lossless, keys, notifier);
 
-            pty::exec(
+            let status = pty::exec(
                 &exec_command,
                 &exec_extra_env,
                 &mut *tty,
                 tty_size,
                 &mut recorder,
             )?;
+
+            recorder.exit(status);
         }
 
         logger::info!("Recording session ended");
@@ -142,7 +150,7 @@ impl Cli {
         Ok((append, overwrite))
     }
 
-    fn open_file(&self, append: bool, overwrite: bool) -> Result<fs::File> {
+    fn open_file(&self, append: bool, overwrite: bool) -> Result<Box<dyn Finish + Send>> {
         let file = fs::OpenOptions::new()
             .write(true)
             .append(append)
@@ -151,28 +159,54 @@ impl Cli {
             .truncate(overwrite)
             .open(&self.filename)?;
 
-        Ok(file)
+        match Compression::from_filename(&self.filename) {
+            Some(compression) => Ok(Box::new(compress::encoder(file, compression)?)),
+            None => Ok(Box::new(file)),
+        }
     }
 
     fn get_output(
         &self,
-        file: fs::File,
+        file: Box<dyn Finish + Send>,
         append: bool,
         config: &Config,
     ) -> Result<Box<dyn recorder::Output + Send>> {
-        let format = self.format.unwrap_or_else(|| {
-            if self.raw {
-                Format::Raw
-            } else if self.filename.to_lowercase().ends_with(".txt") {
-                Format::Txt
-            } else {
-                Format::Asciicast
+        let format = match self.format {
+            Some(format) => format,
+
+            None => {
+                if self.raw {
+                    Format::Raw
+                } else if compress::strip_extension(&self.filename)
+                    .to_lowercase()
+                    .ends_with(".txt")
+                {
+                    Format::Txt
+                } else if compress::strip_extension(&self.filename)
+                    .to_lowercase()
+                    .ends_with(".ttyrec")
+                {
+                    Format::Ttyrec
+                } else if append && asciicast::get_version(&self.filename)? == 3 {
+                    Format::AsciicastV3
+                } else {
+                    Format::Asciicast
+                }
             }
-        });
+        };
 
         match format {
-            Format::Asciicast => {
+            Format::Asciicast | Format::AsciicastV3 => {
+                let version = match format {
+                    Format::AsciicastV3 => asciicast::Version::V3,
+                    _ => asciicast::Version::V2,
+                };
+
                 let time_offset = if append {
+                    if asciicast::remove_exit_event(&self.filename)? {
+                        logger::info!("Removed exit event from the end of {}", self.filename);
+                    }
+
                     asciicast::get_duration(&self.filename)?
                 } else {
                     0
@@ -182,14 +216,16 @@ impl Cli {
 
                 Ok(Box::new(encoder::AsciicastEncoder::new(
                     file,
+                    version,
                     append,
                     time_offset,
                     metadata,
                 )))
             }
 
-            Format::Raw => Ok(Box::new(encoder::RawEncoder::new(file, append))),
+            Format::Raw => Ok(Box::new(encoder::RawEncoder::new(file, append, None))),
             Format::Txt => Ok(Box::new(encoder::TextEncoder::new(file))),
+            Format::Ttyrec => Ok(Box::new(encoder::TtyrecEncoder::new(file, append))),
         }
     }
 
@@ -209,10 +245,14 @@ impl Cli {
             .unwrap_or(String::from("TERM,SHELL"));
 
         encoder::Metadata {
+            term_type: env::var("TERM").ok(),
+            term_version: None,
             idle_time_limit,
             command,
             title: self.title.clone(),
             env: Some(capture_env(&env)),
+            theme: None,
+            extra: Default::default(),
         }
     }
 
@@ -224,33 +264,6 @@ impl Cli {
     }
 }
 
-fn parse_tty_size(s: &str) -> Result<TtySize> {
-    match s.split_once('x') {
-        Some((cols, "")) => {
-            let cols: u16 = cols.parse()?;
-
-            Ok(TtySize((Some(cols), None)))
-        }
-
-        Some(("", rows)) => {
-            let rows: u16 = rows.parse()?;
-
-            Ok(TtySize((None, Some(rows))))
-        }
-
-        Some((cols, rows)) => {
-            let cols: u16 = cols.parse()?;
-            let rows: u16 = rows.parse()?;
-
-            Ok(TtySize((Some(cols), Some(rows))))
-        }
-
-        None => {
-            bail!("{s}")
-        }
-    }
-}
-
 fn get_key_bindings(config: &Config) -> Result<KeyBindings> {
     let mut keys = KeyBindings::default();
 
@@ -0,0 +1,168 @@
+use crate::asciicast;
+use crate::compress::{self, Compression};
+use crate::encoder::{self, EncoderExt, Finish};
+use crate::logger;
+use anyhow::{bail, Result};
+use clap::Args;
+use std::cell::Cell;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+use tempfile::NamedTempFile;
+
+#[derive(Debug, Args)]
+pub struct Cli {
+    input_filename: String,
+
+    /// Output filename [default: repair the input file in place]
+    output_filename: Option<String>,
+
+    /// Overwrite target file if it already exists
+    #[arg(long)]
+    overwrite: bool,
+}
+
+impl Cli {
+    pub fn run(self) -> Result<()> {
+        let output_filename = self.get_output_filename()?;
+        let damaged = Rc::new(Cell::new(0));
+        let counter = damaged.clone();
+
+        let recording = asciicast::open_from_path_tolerant(&self.input_filename, move |e| {
+            logger::info!("Skipping damaged data at {}", e);
+            counter.set(counter.get() + 1);
+        })?;
+
+        let version = if recording.header.version == 3 {
+            asciicast::Version::V3
+        } else {
+            asciicast::Version::V2
+        };
+
+        let dir = Path::new(output_filename)
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+
+        let file = NamedTempFile::new_in(dir)?;
+
+        {
+            let writer: Box<dyn Finish + Send> = match Compression::from_filename(output_filename) {
+                Some(compression) => Box::new(compress::encoder(file.reopen()?, compression)?),
+                None => Box::new(file.reopen()?),
+            };
+
+            let mut encoder = encoder::AsciicastEncoder::new(
+                writer,
+                version,
+                false,
+                0,
+                (&recording.header).into(),
+            );
+
+            encoder.encode(recording)?;
+        }
+
+        fs::set_permissions(
+            file.path(),
+            fs::metadata(&self.input_filename)?.permissions(),
+        )?;
+        file.persist(output_filename)?;
+
+        match damaged.get() {
+            0 => logger::info!("No damage found, recording written to {}", output_filename),
+            n => logger::info!(
+                "Skipped {} damaged line(s), repaired recording written to {}",
+                n,
+                output_filename
+            ),
+        }
+
+        Ok(())
+    }
+
+    fn get_output_filename(&self) -> Result<&str> {
+        match &self.output_filename {
+            Some(filename) => {
+                let path = Path::new(filename);
+
+                if path.exists() && !self.overwrite && path.metadata()?.len() > 0 {
+                    bail!("file exists, use --overwrite option to overwrite the file");
+                }
+
+                Ok(filename)
+            }
+
+            None => Ok(&self.input_filename),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cli;
+    use flate2::write::GzEncoder;
+    use std::fs;
+    use std::io::Write;
+
+    #[test]
+    fn repair() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("repaired.cast");
+
+        let cli = Cli {
+            input_filename: "tests/casts/damaged.cast".to_owned(),
+            output_filename: Some(output.to_str().unwrap().to_owned()),
+            overwrite: false,
+        };
+
+        cli.run().unwrap();
+
+        let lines: Vec<serde_json::Value> = fs::read_to_string(&output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["version"], 2);
+        assert_eq!(lines[0]["width"], 80);
+        assert_eq!(lines[1], serde_json::json!([1.0, "o", "foo"]));
+        assert_eq!(lines[2], serde_json::json!([2.0, "o", "bar"]));
+    }
+
+    #[test]
+    fn repair_truncated_gzip() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("damaged.cast.gz");
+        let output = dir.path().join("repaired.cast");
+
+        let mut gz = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(b"{\"version\": 2, \"width\": 80, \"height\": 24}\n[1.0, \"o\", \"foo\"]\n")
+            .unwrap();
+        gz.flush().unwrap();
+        gz.write_all(b"[2.0, \"o\", \"bar\"]\n").unwrap();
+        gz.flush().unwrap();
+
+        // cut off in the middle of the last deflate block, with no trailer
+        let data = gz.get_ref();
+        fs::write(&input, &data[..data.len() - 8]).unwrap();
+
+        let cli = Cli {
+            input_filename: input.to_str().unwrap().to_owned(),
+            output_filename: Some(output.to_str().unwrap().to_owned()),
+            overwrite: false,
+        };
+
+        cli.run().unwrap();
+
+        let lines: Vec<serde_json::Value> = fs::read_to_string(&output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1], serde_json::json!([1.0, "o", "foo"]));
+    }
+}
@@ -1,18 +1,30 @@
 use crate::asciicast;
+use crate::compress;
 use crate::config::Config;
+use crate::encoder::{self, EncoderExt};
+use crate::util;
 use anyhow::{anyhow, bail, Result};
 use clap::Args;
 use reqwest::{
-    blocking::{multipart::Form, Client},
+    blocking::{
+        multipart::{Form, Part},
+        Client,
+    },
     header, Url,
 };
 use serde::Deserialize;
 use std::env;
+use std::fs;
+use std::path::Path;
 
 #[derive(Debug, Args)]
 pub struct Cli {
     /// Filename/path of asciicast to upload
     filename: String,
+
+    /// Override terminal size of the recording (e.g. for ttyrec files)
+    #[arg(long, value_parser = util::parse_tty_size, value_name = "COLSxROWS")]
+    tty_size: Option<util::TtySize>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -23,9 +35,20 @@ struct UploadResponse {
 
 impl Cli {
     pub fn run(self, config: &Config) -> Result<()> {
-        let _ = asciicast::open_from_path(&self.filename)?;
+        let mut recording = asciicast::open_from_path(&self.filename)?;
         let client = Client::new();
-        let form = Form::new().file("asciicast", self.filename)?;
+
+        if let Some(util::TtySize((cols, rows))) = self.tty_size {
+            recording.header.cols = cols.unwrap_or(recording.header.cols);
+            recording.header.rows = rows.unwrap_or(recording.header.rows);
+        }
+
+        // the original file is uploaded as is unless it needs converting
+        let form = if recording.header.version == 0 || self.tty_size.is_some() {
+            build_converted_form(&self.filename, recording)?
+        } else {
+            build_form(&self.filename)?
+        };
 
         let response = client
             .post(api_url(&config.get_server_url()?))
@@ -63,6 +86,57 @@ impl Cli {
     }
 }
 
+fn build_form(filename: &str) -> Result<Form> {
+    if compress::is_compressed(fs::File::open(filename)?)? {
+        let reader = compress::decoder(fs::File::open(filename)?)?;
+        let name = Path::new(compress::strip_extension(filename))
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or("asciicast.cast".to_owned());
+
+        let part = Part::reader(reader).file_name(name);
+
+        Ok(Form::new().part("asciicast", part))
+    } else {
+        Ok(Form::new().file("asciicast", filename)?)
+    }
+}
+
+/// Builds the form with the recording converted to asciicast (v3 for v3
+/// recordings, v2 otherwise), for recordings imported from other formats or
+/// with an overridden terminal size.
+fn build_converted_form(filename: &str, recording: asciicast::Asciicast) -> Result<Form> {
+    let mut data = Vec::new();
+
+    let version = if recording.header.version == 3 {
+        asciicast::Version::V3
+    } else {
+        asciicast::Version::V2
+    };
+
+    {
+        let mut encoder = encoder::AsciicastEncoder::new(
+            &mut data,
+            version,
+            false,
+            0,
+            (&recording.header).into(),
+        );
+
+        encoder.encode(recording)?;
+    }
+
+    let name = Path::new(compress::strip_extension(filename))
+        .with_extension("cast")
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or("asciicast.cast".to_owned());
+
+    let part = Part::bytes(data).file_name(name);
+
+    Ok(Form::new().part("asciicast", part))
+}
+
 fn api_url(server_url: &Url) -> Url {
     let mut url = server_url.clone();
     url.set_path("api/asciicasts");
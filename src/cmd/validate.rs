@@ -0,0 +1,65 @@
+use crate::asciicast;
+use crate::compress;
+use crate::logger;
+use anyhow::{bail, Result};
+use clap::Args;
+use std::fs;
+
+#[derive(Debug, Args)]
+pub struct Cli {
+    #[arg(required = true)]
+    filenames: Vec<String>,
+}
+
+impl Cli {
+    pub fn run(self) -> Result<()> {
+        let mut invalid = 0;
+
+        for filename in &self.filenames {
+            let report = match fs::File::open(filename).and_then(compress::decoder) {
+                Ok(reader) => asciicast::validate(reader),
+
+                Err(e) => {
+                    println!("{filename}: {e}");
+                    invalid += 1;
+
+                    continue;
+                }
+            };
+
+            for problem in &report.errors {
+                print_problem(filename, "", problem);
+            }
+
+            for problem in &report.warnings {
+                print_problem(filename, "warning: ", problem);
+            }
+
+            if !report.errors.is_empty() {
+                invalid += 1;
+            }
+        }
+
+        if invalid > 0 {
+            bail!("{} of {} file(s) invalid", invalid, self.filenames.len());
+        }
+
+        logger::info!("{} file(s) valid", self.filenames.len());
+
+        Ok(())
+    }
+}
+
+fn print_problem(filename: &str, prefix: &str, problem: &asciicast::ParseError) {
+    match problem.column {
+        Some(column) => println!(
+            "{}:{}:{}: {}{}",
+            filename, problem.line, column, prefix, problem.message
+        ),
+
+        None => println!(
+            "{}:{}: {}{}",
+            filename, problem.line, prefix, problem.message
+        ),
+    }
+}
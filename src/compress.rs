@@ -0,0 +1,267 @@
+use crate::encoder::Finish;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use std::io::{self, BufRead, Read, Write};
+use std::time::{Duration, Instant};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn from_filename(filename: &str) -> Option<Self> {
+        let filename = filename.to_lowercase();
+
+        if filename.ends_with(".gz") {
+            Some(Compression::Gzip)
+        } else if filename.ends_with(".zst") {
+            Some(Compression::Zstd)
+        } else {
+            None
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+        }
+    }
+}
+
+/// Returns the filename without its compression extension (if any), e.g.
+/// `demo.txt` for `demo.txt.gz`.
+pub fn strip_extension(filename: &str) -> &str {
+    match Compression::from_filename(filename) {
+        Some(compression) => &filename[..filename.len() - compression.extension().len()],
+        None => filename,
+    }
+}
+
+/// Wraps the reader in a decompressor if its content starts with a gzip or
+/// zstd magic number, otherwise returns it buffered as is.
+pub fn decoder<R: Read + Send + 'static>(reader: R) -> io::Result<Box<dyn BufRead + Send>> {
+    let mut reader = io::BufReader::new(reader);
+    let magic = reader.fill_buf()?;
+
+    if magic.starts_with(GZIP_MAGIC) {
+        Ok(Box::new(io::BufReader::new(MultiGzDecoder::new(reader))))
+    } else if magic.starts_with(ZSTD_MAGIC) {
+        Ok(Box::new(io::BufReader::new(zstd::Decoder::with_buffer(
+            reader,
+        )?)))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+pub fn is_compressed<R: Read>(reader: R) -> io::Result<bool> {
+    let mut magic = Vec::with_capacity(ZSTD_MAGIC.len());
+    reader
+        .take(ZSTD_MAGIC.len() as u64)
+        .read_to_end(&mut magic)?;
+
+    Ok(magic.starts_with(GZIP_MAGIC) || magic.starts_with(ZSTD_MAGIC))
+}
+
+/// Wraps the writer in a streaming compressor.
+pub fn encoder<W: Write>(writer: W, compression: Compression) -> io::Result<Encoder<W>> {
+    let inner = match compression {
+        Compression::Gzip => Inner::Gzip(GzEncoder::new(writer, flate2::Compression::default())),
+        Compression::Zstd => Inner::Zstd(zstd::Encoder::new(writer, 0)?),
+    };
+
+    Ok(Encoder {
+        inner,
+        last_sync: Instant::now(),
+    })
+}
+
+/// Streaming compressor. The compressed stream is completed by `finish`,
+/// which reports errors dropping it would ignore (e.g. a full disk).
+///
+/// Flushes are turned into sync points at most every `SYNC_INTERVAL`, so
+/// that line-buffered writers don't force one (and a drop in compression
+/// ratio) after every line, while an interrupted recording still has most
+/// of its data readable.
+pub struct Encoder<W: Write> {
+    inner: Inner<W>,
+    last_sync: Instant,
+}
+
+enum Inner<W: Write> {
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            Inner::Gzip(encoder) => encoder.write(buf),
+            Inner::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.last_sync.elapsed() < SYNC_INTERVAL {
+            return Ok(());
+        }
+
+        self.last_sync = Instant::now();
+
+        match &mut self.inner {
+            Inner::Gzip(encoder) => encoder.flush(),
+            Inner::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+impl<W: Write> Finish for Encoder<W> {
+    fn finish(&mut self) -> io::Result<()> {
+        match &mut self.inner {
+            Inner::Gzip(encoder) => {
+                encoder.try_finish()?;
+                encoder.get_mut().flush()
+            }
+
+            Inner::Zstd(encoder) => {
+                encoder.do_finish()?;
+                encoder.get_mut().flush()
+            }
+        }
+    }
+}
+
+// GzEncoder finishes on drop by itself
+impl<W: Write> Drop for Encoder<W> {
+    fn drop(&mut self) {
+        if let Inner::Zstd(encoder) = &mut self.inner {
+            let _ = encoder.do_finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Compression;
+    use crate::asciicast;
+    use crate::encoder::Finish;
+    use std::cell::Cell;
+    use std::fs;
+    use std::io::{self, Read, Write};
+    use std::rc::Rc;
+
+    #[test]
+    fn round_trip() {
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let file = tempfile::NamedTempFile::new().unwrap();
+
+            // concatenated streams, as produced by rec --append
+            for chunk in ["hello\n", "world\n"] {
+                let f = fs::OpenOptions::new()
+                    .append(true)
+                    .open(file.path())
+                    .unwrap();
+
+                let mut encoder = super::encoder(f, compression).unwrap();
+                encoder.write_all(chunk.as_bytes()).unwrap();
+                encoder.finish().unwrap();
+            }
+
+            let f = fs::File::open(file.path()).unwrap();
+            assert!(super::is_compressed(&f).unwrap());
+
+            let mut output = String::new();
+
+            super::decoder(fs::File::open(file.path()).unwrap())
+                .unwrap()
+                .read_to_string(&mut output)
+                .unwrap();
+
+            assert_eq!(output, "hello\nworld\n");
+        }
+    }
+
+    #[test]
+    fn interrupted() {
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let file = tempfile::NamedTempFile::new().unwrap();
+            let mut encoder = super::encoder(file.reopen().unwrap(), compression).unwrap();
+            encoder.last_sync -= super::SYNC_INTERVAL;
+
+            encoder
+                .write_all(b"{\"version\": 2, \"width\": 80, \"height\": 24}\n")
+                .unwrap();
+            encoder.write_all(b"[1.0, \"o\", \"foo\"]\n").unwrap();
+            encoder.flush().unwrap();
+            encoder.write_all(b"[2.0, \"o\", \"bar\"]\n").unwrap();
+
+            // as if the recording process got killed, without finishing
+            // (or even dropping) the encoder
+            std::mem::forget(encoder);
+
+            let damaged = Rc::new(Cell::new(0));
+            let counter = damaged.clone();
+
+            let recording = asciicast::open_from_path_tolerant(file.path(), move |_| {
+                counter.set(counter.get() + 1);
+            })
+            .unwrap();
+
+            let events = recording
+                .events
+                .collect::<anyhow::Result<Vec<_>>>()
+                .unwrap();
+
+            assert_eq!(events, vec![asciicast::Event::output(1_000_000, b"foo")]);
+            assert_eq!(damaged.get(), 1);
+        }
+    }
+
+    #[test]
+    fn finish_error() {
+        struct Full;
+
+        impl Write for Full {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                Err(io::Error::other("no space left on device"))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let mut encoder = super::encoder(Full, compression).unwrap();
+            let result = encoder.write_all(b"hello").and_then(|_| encoder.finish());
+
+            assert!(result.is_err());
+        }
+    }
+
+    #[test]
+    fn decoder_passthrough() {
+        let mut output = String::new();
+
+        super::decoder(io::Cursor::new(b"hello".to_vec()))
+            .unwrap()
+            .read_to_string(&mut output)
+            .unwrap();
+
+        assert_eq!(output, "hello");
+    }
+
+    #[test]
+    fn strip_extension() {
+        assert_eq!(super::strip_extension("demo.cast.gz"), "demo.cast");
+        assert_eq!(super::strip_extension("demo.txt.ZST"), "demo.txt");
+        assert_eq!(super::strip_extension("demo.cast"), "demo.cast");
+    }
+}
@@ -263,23 +263,19 @@ fn parse_key<S: AsRef<str>>(key: S) -> Result<Key> {
             return Ok(Some(str.as_bytes().into()));
         }
 
-        2 => {
-            if chars[0] == '^' && chars[1].is_ascii_alphabetic() {
-                let key = vec![chars[1].to_ascii_uppercase() as u8 - 0x40];
+        2 if chars[0] == '^' && chars[1].is_ascii_alphabetic() => {
+            let key = vec![chars[1].to_ascii_uppercase() as u8 - 0x40];
 
-                return Ok(Some(key));
-            }
+            return Ok(Some(key));
         }
 
-        3 => {
-            if chars[0].to_ascii_uppercase() == 'C'
-                && ['+', '-'].contains(&chars[1])
-                && chars[2].is_ascii_alphabetic()
-            {
-                let key = vec![chars[2].to_ascii_uppercase() as u8 - 0x40];
+        3 if chars[0].eq_ignore_ascii_case(&'C')
+            && ['+', '-'].contains(&chars[1])
+            && chars[2].is_ascii_alphabetic() =>
+        {
+            let key = vec![chars[2].to_ascii_uppercase() as u8 - 0x40];
 
-                return Ok(Some(key));
-            }
+            return Ok(Some(key));
         }
 
         _ => (),
@@ -0,0 +1,202 @@
+use super::Finish;
+use crate::asciicast::{Event, EventData};
+use crate::tty;
+use avt::{Color, Pen};
+use std::io;
+
+/// Writes the final terminal contents, scrollback followed by the screen,
+/// as text with SGR sequences rebuilt from cell attributes. Each line starts
+/// with default attributes, and attributes are set (always from reset) only
+/// where they change, so the output is stable for diffing.
+pub struct AnsiEncoder<W: Finish> {
+    writer: W,
+    vt: Option<avt::Vt>,
+}
+
+impl<W: Finish> AnsiEncoder<W> {
+    pub fn new(writer: W) -> Self {
+        AnsiEncoder { writer, vt: None }
+    }
+
+    fn write_line(&mut self, line: &avt::Line) -> io::Result<()> {
+        let default_pen = Pen::default();
+        let mut pen = default_pen;
+
+        let mut cells: Vec<(char, Pen)> = line.cells().collect();
+
+        while let Some((' ', p)) = cells.last() {
+            if p.background().is_some()
+                || p.is_underline()
+                || p.is_strikethrough()
+                || p.is_inverse()
+            {
+                break;
+            }
+
+            cells.pop();
+        }
+
+        let mut text = String::new();
+
+        for (ch, cell_pen) in cells {
+            if cell_pen != pen {
+                text.push_str(&sgr(&cell_pen));
+                pen = cell_pen;
+            }
+
+            text.push(ch);
+        }
+
+        if pen != default_pen {
+            text.push_str("\x1b[0m");
+        }
+
+        text.push('\n');
+
+        self.writer.write_all(text.as_bytes())
+    }
+}
+
+impl<W: Finish> super::Encoder for AnsiEncoder<W> {
+    fn start(&mut self, _timestamp: Option<u64>, tty_size: &tty::TtySize) -> io::Result<()> {
+        self.vt = Some(
+            avt::Vt::builder()
+                .size(tty_size.0 as usize, tty_size.1 as usize)
+                .resizable(true)
+                .build(),
+        );
+
+        Ok(())
+    }
+
+    fn event(&mut self, event: &Event) -> io::Result<()> {
+        let vt = self.vt.as_mut().unwrap();
+
+        match &event.data {
+            EventData::Output(data) => {
+                vt.feed_str(data);
+            }
+
+            EventData::Bytes(data) => {
+                vt.feed_str(&String::from_utf8_lossy(data));
+            }
+
+            EventData::Resize(cols, rows) => {
+                vt.feed_str(&format!("\x1b[8;{rows};{cols}t"));
+            }
+
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let vt = self.vt.take().unwrap();
+        let lines = vt.lines();
+
+        let len = lines
+            .iter()
+            .rposition(|line| {
+                line.cells()
+                    .any(|(ch, pen)| ch != ' ' || pen != Pen::default())
+            })
+            .map_or(0, |i| i + 1);
+
+        for line in &lines[..len] {
+            self.write_line(line)?;
+        }
+
+        self.writer.finish()
+    }
+}
+
+/// Returns SGR sequence setting the pen's attributes, starting from reset.
+fn sgr(pen: &Pen) -> String {
+    let mut params = vec!["0".to_owned()];
+
+    if pen.is_bold() {
+        params.push("1".to_owned());
+    }
+
+    if pen.is_faint() {
+        params.push("2".to_owned());
+    }
+
+    if pen.is_italic() {
+        params.push("3".to_owned());
+    }
+
+    if pen.is_underline() {
+        params.push("4".to_owned());
+    }
+
+    if pen.is_blink() {
+        params.push("5".to_owned());
+    }
+
+    if pen.is_inverse() {
+        params.push("7".to_owned());
+    }
+
+    if pen.is_strikethrough() {
+        params.push("9".to_owned());
+    }
+
+    if let Some(color) = pen.foreground() {
+        params.push(color_params(color, 30));
+    }
+
+    if let Some(color) = pen.background() {
+        params.push(color_params(color, 40));
+    }
+
+    format!("\x1b[{}m", params.join(";"))
+}
+
+fn color_params(color: Color, base: u8) -> String {
+    match color {
+        Color::Indexed(i) if i < 8 => format!("{}", base + i),
+        Color::Indexed(i) if i < 16 => format!("{}", base + 60 + i - 8),
+        Color::Indexed(i) => format!("{};5;{}", base + 8, i),
+        Color::RGB(c) => format!("{};2;{};{};{}", base + 8, c.r, c.g, c.b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AnsiEncoder;
+    use crate::asciicast::Event;
+    use crate::encoder::Encoder;
+    use crate::tty::TtySize;
+
+    #[test]
+    fn encoder_impl() {
+        let mut out: Vec<u8> = Vec::new();
+        let mut enc = AnsiEncoder::new(&mut out);
+
+        enc.start(None, &TtySize(12, 2)).unwrap();
+        enc.event(&Event::output(
+            0,
+            b"\x1b[1m\x1b[31mFAIL\x1b[39m test\x1b[m  \r\n",
+        ))
+        .unwrap();
+        enc.event(&Event::output(
+            1,
+            b"\x1b[38;5;208mok\x1b[0m \x1b[44m  \x1b[0m\r\n",
+        ))
+        .unwrap();
+        enc.event(&Event::output(2, b"\x1b[92;48;2;1;2;3mx\x1b[m\r\n\r\n"))
+            .unwrap();
+        enc.finish().unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                "\x1b[0;1;31mFAIL\x1b[0;1m test\x1b[0m\n",
+                "\x1b[0;38;5;208mok\x1b[0m \x1b[0;44m  \x1b[0m\n",
+                "\x1b[0;92;48;2;1;2;3mx\x1b[0m\n",
+            )
+        );
+    }
+}
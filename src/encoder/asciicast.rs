@@ -1,50 +1,83 @@
-use crate::asciicast::{Event, Header, Writer};
+use super::Finish;
+use crate::asciicast::{Event, Header, Theme, V1Writer, V3Writer, Version, Writer};
 use crate::tty;
 use std::collections::HashMap;
-use std::io::{self, Write};
+use std::io;
 
-pub struct AsciicastEncoder<W: Write> {
-    writer: Writer<W>,
+pub struct AsciicastEncoder<W: Finish> {
+    writer: VersionedWriter<W>,
     append: bool,
     metadata: Metadata,
 }
 
+enum VersionedWriter<W: Finish> {
+    V1(Box<V1Writer<W>>),
+    V2(Writer<W>),
+    V3(V3Writer<W>),
+}
+
 pub struct Metadata {
+    pub term_type: Option<String>,
+    pub term_version: Option<String>,
     pub idle_time_limit: Option<f64>,
     pub command: Option<String>,
     pub title: Option<String>,
     pub env: Option<HashMap<String, String>>,
+    pub theme: Option<Theme>,
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 impl<W> AsciicastEncoder<W>
 where
-    W: Write,
+    W: Finish,
 {
-    pub fn new(writer: W, append: bool, time_offset: u64, metadata: Metadata) -> Self {
+    pub fn new(
+        writer: W,
+        version: Version,
+        append: bool,
+        time_offset: u64,
+        metadata: Metadata,
+    ) -> Self {
+        let writer = match version {
+            Version::V1 => VersionedWriter::V1(Box::new(V1Writer::new(writer))),
+            Version::V2 => VersionedWriter::V2(Writer::new(writer, time_offset)),
+            Version::V3 => VersionedWriter::V3(V3Writer::new(writer, time_offset)),
+        };
+
         Self {
-            writer: Writer::new(writer, time_offset),
+            writer,
             append,
             metadata,
         }
     }
 
     fn build_header(&self, timestamp: Option<u64>, tty_size: &tty::TtySize) -> Header {
+        let version = match self.writer {
+            VersionedWriter::V1(_) => 1,
+            VersionedWriter::V2(_) => 2,
+            VersionedWriter::V3(_) => 3,
+        };
+
         Header {
-            version: 2,
+            version,
             cols: tty_size.0,
             rows: tty_size.1,
+            term_type: self.metadata.term_type.clone(),
+            term_version: self.metadata.term_version.clone(),
             timestamp,
             idle_time_limit: self.metadata.idle_time_limit,
             command: self.metadata.command.clone(),
             title: self.metadata.title.clone(),
             env: self.metadata.env.clone(),
+            theme: self.metadata.theme.clone(),
+            extra: self.metadata.extra.clone(),
         }
     }
 }
 
 impl<W> super::Encoder for AsciicastEncoder<W>
 where
-    W: Write,
+    W: Finish,
 {
     fn start(&mut self, timestamp: Option<u64>, tty_size: &tty::TtySize) -> io::Result<()> {
         if self.append {
@@ -52,22 +85,46 @@ where
         } else {
             let header = self.build_header(timestamp, tty_size);
 
-            self.writer.write_header(&header)
+            match &mut self.writer {
+                VersionedWriter::V1(writer) => writer.write_header(&header),
+                VersionedWriter::V2(writer) => writer.write_header(&header),
+                VersionedWriter::V3(writer) => writer.write_header(&header),
+            }
         }
     }
 
     fn event(&mut self, event: &Event) -> io::Result<()> {
-        self.writer.write_event(event)
+        match &mut self.writer {
+            VersionedWriter::V1(writer) => writer.write_event(event),
+            VersionedWriter::V2(writer) => writer.write_event(event),
+            VersionedWriter::V3(writer) => writer.write_event(event),
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        match &mut self.writer {
+            VersionedWriter::V1(writer) => {
+                writer.finish()?;
+                writer.get_mut().finish()
+            }
+
+            VersionedWriter::V2(writer) => writer.get_mut()?.finish(),
+            VersionedWriter::V3(writer) => writer.get_mut()?.finish(),
+        }
     }
 }
 
 impl From<&Header> for Metadata {
     fn from(header: &Header) -> Self {
         Metadata {
+            term_type: header.term_type.as_ref().cloned(),
+            term_version: header.term_version.as_ref().cloned(),
             idle_time_limit: header.idle_time_limit.as_ref().cloned(),
             command: header.command.as_ref().cloned(),
             title: header.title.as_ref().cloned(),
             env: header.env.as_ref().cloned(),
+            theme: header.theme.as_ref().cloned(),
+            extra: header.extra.clone(),
         }
     }
 }
@@ -0,0 +1,295 @@
+// Bitmap font used by image encoders. Printable ASCII glyphs come from the
+// public domain font8x8 by Daniel Hepper (based on the IBM PC BIOS font),
+// box drawing and block elements are synthesized.
+//
+// Each glyph is 8 rows of 8 pixels, with the least significant bit being
+// the leftmost pixel.
+
+pub const WIDTH: usize = 8;
+pub const HEIGHT: usize = 8;
+
+pub type Glyph = [u8; HEIGHT];
+
+const FALLBACK: Glyph = [0x00, 0x7e, 0x42, 0x42, 0x42, 0x42, 0x7e, 0x00];
+
+const ASCII: [Glyph; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // space
+    [0x18, 0x3c, 0x3c, 0x18, 0x18, 0x00, 0x18, 0x00], // !
+    [0x36, 0x36, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // "
+    [0x36, 0x36, 0x7f, 0x36, 0x7f, 0x36, 0x36, 0x00], // #
+    [0x0c, 0x3e, 0x03, 0x1e, 0x30, 0x1f, 0x0c, 0x00], // $
+    [0x00, 0x63, 0x33, 0x18, 0x0c, 0x66, 0x63, 0x00], // %
+    [0x1c, 0x36, 0x1c, 0x6e, 0x3b, 0x33, 0x6e, 0x00], // &
+    [0x06, 0x06, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00], // '
+    [0x18, 0x0c, 0x06, 0x06, 0x06, 0x0c, 0x18, 0x00], // (
+    [0x06, 0x0c, 0x18, 0x18, 0x18, 0x0c, 0x06, 0x00], // )
+    [0x00, 0x66, 0x3c, 0xff, 0x3c, 0x66, 0x00, 0x00], // *
+    [0x00, 0x0c, 0x0c, 0x3f, 0x0c, 0x0c, 0x00, 0x00], // +
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c, 0x06], // ,
+    [0x00, 0x00, 0x00, 0x3f, 0x00, 0x00, 0x00, 0x00], // -
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c, 0x00], // .
+    [0x60, 0x30, 0x18, 0x0c, 0x06, 0x03, 0x01, 0x00], // /
+    [0x3e, 0x63, 0x73, 0x7b, 0x6f, 0x67, 0x3e, 0x00], // 0
+    [0x0c, 0x0e, 0x0c, 0x0c, 0x0c, 0x0c, 0x3f, 0x00], // 1
+    [0x1e, 0x33, 0x30, 0x1c, 0x06, 0x33, 0x3f, 0x00], // 2
+    [0x1e, 0x33, 0x30, 0x1c, 0x30, 0x33, 0x1e, 0x00], // 3
+    [0x38, 0x3c, 0x36, 0x33, 0x7f, 0x30, 0x78, 0x00], // 4
+    [0x3f, 0x03, 0x1f, 0x30, 0x30, 0x33, 0x1e, 0x00], // 5
+    [0x1c, 0x06, 0x03, 0x1f, 0x33, 0x33, 0x1e, 0x00], // 6
+    [0x3f, 0x33, 0x30, 0x18, 0x0c, 0x0c, 0x0c, 0x00], // 7
+    [0x1e, 0x33, 0x33, 0x1e, 0x33, 0x33, 0x1e, 0x00], // 8
+    [0x1e, 0x33, 0x33, 0x3e, 0x30, 0x18, 0x0e, 0x00], // 9
+    [0x00, 0x0c, 0x0c, 0x00, 0x00, 0x0c, 0x0c, 0x00], // :
+    [0x00, 0x0c, 0x0c, 0x00, 0x00, 0x0c, 0x0c, 0x06], // ;
+    [0x18, 0x0c, 0x06, 0x03, 0x06, 0x0c, 0x18, 0x00], // <
+    [0x00, 0x00, 0x3f, 0x00, 0x00, 0x3f, 0x00, 0x00], // =
+    [0x06, 0x0c, 0x18, 0x30, 0x18, 0x0c, 0x06, 0x00], // >
+    [0x1e, 0x33, 0x30, 0x18, 0x0c, 0x00, 0x0c, 0x00], // ?
+    [0x3e, 0x63, 0x7b, 0x7b, 0x7b, 0x03, 0x1e, 0x00], // @
+    [0x0c, 0x1e, 0x33, 0x33, 0x3f, 0x33, 0x33, 0x00], // A
+    [0x3f, 0x66, 0x66, 0x3e, 0x66, 0x66, 0x3f, 0x00], // B
+    [0x3c, 0x66, 0x03, 0x03, 0x03, 0x66, 0x3c, 0x00], // C
+    [0x1f, 0x36, 0x66, 0x66, 0x66, 0x36, 0x1f, 0x00], // D
+    [0x7f, 0x46, 0x16, 0x1e, 0x16, 0x46, 0x7f, 0x00], // E
+    [0x7f, 0x46, 0x16, 0x1e, 0x16, 0x06, 0x0f, 0x00], // F
+    [0x3c, 0x66, 0x03, 0x03, 0x73, 0x66, 0x7c, 0x00], // G
+    [0x33, 0x33, 0x33, 0x3f, 0x33, 0x33, 0x33, 0x00], // H
+    [0x1e, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0x1e, 0x00], // I
+    [0x78, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1e, 0x00], // J
+    [0x67, 0x66, 0x36, 0x1e, 0x36, 0x66, 0x67, 0x00], // K
+    [0x0f, 0x06, 0x06, 0x06, 0x46, 0x66, 0x7f, 0x00], // L
+    [0x63, 0x77, 0x7f, 0x7f, 0x6b, 0x63, 0x63, 0x00], // M
+    [0x63, 0x67, 0x6f, 0x7b, 0x73, 0x63, 0x63, 0x00], // N
+    [0x1c, 0x36, 0x63, 0x63, 0x63, 0x36, 0x1c, 0x00], // O
+    [0x3f, 0x66, 0x66, 0x3e, 0x06, 0x06, 0x0f, 0x00], // P
+    [0x1e, 0x33, 0x33, 0x33, 0x3b, 0x1e, 0x38, 0x00], // Q
+    [0x3f, 0x66, 0x66, 0x3e, 0x36, 0x66, 0x67, 0x00], // R
+    [0x1e, 0x33, 0x07, 0x0e, 0x38, 0x33, 0x1e, 0x00], // S
+    [0x3f, 0x2d, 0x0c, 0x0c, 0x0c, 0x0c, 0x1e, 0x00], // T
+    [0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x3f, 0x00], // U
+    [0x33, 0x33, 0x33, 0x33, 0x33, 0x1e, 0x0c, 0x00], // V
+    [0x63, 0x63, 0x63, 0x6b, 0x7f, 0x77, 0x63, 0x00], // W
+    [0x63, 0x63, 0x36, 0x1c, 0x1c, 0x36, 0x63, 0x00], // X
+    [0x33, 0x33, 0x33, 0x1e, 0x0c, 0x0c, 0x1e, 0x00], // Y
+    [0x7f, 0x63, 0x31, 0x18, 0x4c, 0x66, 0x7f, 0x00], // Z
+    [0x1e, 0x06, 0x06, 0x06, 0x06, 0x06, 0x1e, 0x00], // [
+    [0x03, 0x06, 0x0c, 0x18, 0x30, 0x60, 0x40, 0x00], // \
+    [0x1e, 0x18, 0x18, 0x18, 0x18, 0x18, 0x1e, 0x00], // ]
+    [0x08, 0x1c, 0x36, 0x63, 0x00, 0x00, 0x00, 0x00], // ^
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff], // _
+    [0x0c, 0x0c, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00], // `
+    [0x00, 0x00, 0x1e, 0x30, 0x3e, 0x33, 0x6e, 0x00], // a
+    [0x07, 0x06, 0x06, 0x3e, 0x66, 0x66, 0x3b, 0x00], // b
+    [0x00, 0x00, 0x1e, 0x33, 0x03, 0x33, 0x1e, 0x00], // c
+    [0x38, 0x30, 0x30, 0x3e, 0x33, 0x33, 0x6e, 0x00], // d
+    [0x00, 0x00, 0x1e, 0x33, 0x3f, 0x03, 0x1e, 0x00], // e
+    [0x1c, 0x36, 0x06, 0x0f, 0x06, 0x06, 0x0f, 0x00], // f
+    [0x00, 0x00, 0x6e, 0x33, 0x33, 0x3e, 0x30, 0x1f], // g
+    [0x07, 0x06, 0x36, 0x6e, 0x66, 0x66, 0x67, 0x00], // h
+    [0x0c, 0x00, 0x0e, 0x0c, 0x0c, 0x0c, 0x1e, 0x00], // i
+    [0x30, 0x00, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1e], // j
+    [0x07, 0x06, 0x66, 0x36, 0x1e, 0x36, 0x67, 0x00], // k
+    [0x0e, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0x1e, 0x00], // l
+    [0x00, 0x00, 0x33, 0x7f, 0x7f, 0x6b, 0x63, 0x00], // m
+    [0x00, 0x00, 0x1f, 0x33, 0x33, 0x33, 0x33, 0x00], // n
+    [0x00, 0x00, 0x1e, 0x33, 0x33, 0x33, 0x1e, 0x00], // o
+    [0x00, 0x00, 0x3b, 0x66, 0x66, 0x3e, 0x06, 0x0f], // p
+    [0x00, 0x00, 0x6e, 0x33, 0x33, 0x3e, 0x30, 0x78], // q
+    [0x00, 0x00, 0x3b, 0x6e, 0x66, 0x06, 0x0f, 0x00], // r
+    [0x00, 0x00, 0x3e, 0x03, 0x1e, 0x30, 0x1f, 0x00], // s
+    [0x08, 0x0c, 0x3e, 0x0c, 0x0c, 0x2c, 0x18, 0x00], // t
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x33, 0x6e, 0x00], // u
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x1e, 0x0c, 0x00], // v
+    [0x00, 0x00, 0x63, 0x6b, 0x7f, 0x7f, 0x36, 0x00], // w
+    [0x00, 0x00, 0x63, 0x36, 0x1c, 0x36, 0x63, 0x00], // x
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x3e, 0x30, 0x1f], // y
+    [0x00, 0x00, 0x3f, 0x19, 0x0c, 0x26, 0x3f, 0x00], // z
+    [0x38, 0x0c, 0x0c, 0x07, 0x0c, 0x0c, 0x38, 0x00], // {
+    [0x18, 0x18, 0x18, 0x00, 0x18, 0x18, 0x18, 0x00], // |
+    [0x07, 0x0c, 0x0c, 0x38, 0x0c, 0x0c, 0x07, 0x00], // }
+    [0x6e, 0x3b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ~
+];
+
+// Box drawing arms: left, right, up, down.
+const LEFT: u8 = 1;
+const RIGHT: u8 = 1 << 1;
+const UP: u8 = 1 << 2;
+const DOWN: u8 = 1 << 3;
+
+// Row and column where box drawing lines start.
+const CENTER: usize = 3;
+
+pub fn glyph(ch: char) -> Glyph {
+    match ch {
+        ' '..='~' => ASCII[ch as usize - 0x20],
+        '\u{a0}' => ASCII[0],
+        '\u{2500}'..='\u{257f}' => box_drawing(ch).unwrap_or(FALLBACK),
+        '\u{2580}'..='\u{259f}' => block(ch).unwrap_or(FALLBACK),
+        _ => FALLBACK,
+    }
+}
+
+fn box_drawing(ch: char) -> Option<Glyph> {
+    let (arms, heavy) = match ch {
+        '─' => (LEFT | RIGHT, false),
+        '━' | '═' => (LEFT | RIGHT, true),
+        '│' => (UP | DOWN, false),
+        '┃' | '║' => (UP | DOWN, true),
+        '┌' | '╭' => (RIGHT | DOWN, false),
+        '┏' | '╔' => (RIGHT | DOWN, true),
+        '┐' | '╮' => (LEFT | DOWN, false),
+        '┓' | '╗' => (LEFT | DOWN, true),
+        '└' | '╰' => (RIGHT | UP, false),
+        '┗' | '╚' => (RIGHT | UP, true),
+        '┘' | '╯' => (LEFT | UP, false),
+        '┛' | '╝' => (LEFT | UP, true),
+        '├' => (RIGHT | UP | DOWN, false),
+        '┣' | '╠' => (RIGHT | UP | DOWN, true),
+        '┤' => (LEFT | UP | DOWN, false),
+        '┫' | '╣' => (LEFT | UP | DOWN, true),
+        '┬' => (LEFT | RIGHT | DOWN, false),
+        '┳' | '╦' => (LEFT | RIGHT | DOWN, true),
+        '┴' => (LEFT | RIGHT | UP, false),
+        '┻' | '╩' => (LEFT | RIGHT | UP, true),
+        '┼' => (LEFT | RIGHT | UP | DOWN, false),
+        '╋' | '╬' => (LEFT | RIGHT | UP | DOWN, true),
+        '╴' => (LEFT, false),
+        '╵' => (UP, false),
+        '╶' => (RIGHT, false),
+        '╷' => (DOWN, false),
+        _ => return None,
+    };
+
+    let thickness = if heavy { 2 } else { 1 };
+    let mut glyph = [0; HEIGHT];
+
+    for t in 0..thickness {
+        let row = CENTER + t;
+        let col = 1 << (CENTER + t);
+
+        if arms & LEFT != 0 {
+            glyph[row] |= (1 << (CENTER + thickness)) - 1;
+        }
+
+        if arms & RIGHT != 0 {
+            glyph[row] |= !((1 << CENTER) - 1);
+        }
+
+        for (i, line) in glyph.iter_mut().enumerate() {
+            if (arms & UP != 0 && i < CENTER + thickness) || (arms & DOWN != 0 && i >= CENTER) {
+                *line |= col;
+            }
+        }
+    }
+
+    Some(glyph)
+}
+
+fn block(ch: char) -> Option<Glyph> {
+    let n = ch as usize;
+    let mut glyph = [0; HEIGHT];
+
+    match ch {
+        // upper half
+        '▀' => glyph[..HEIGHT / 2].fill(0xff),
+
+        // lower 1/8 to full block
+        '▁'..='█' => glyph[HEIGHT - (n - 0x2580)..].fill(0xff),
+
+        // left 7/8 to left 1/8
+        '▉'..='▏' => glyph.fill((1 << (0x2590 - n)) - 1),
+
+        // right half
+        '▐' => glyph.fill(0xf0),
+
+        '░' => {
+            for (i, line) in glyph.iter_mut().enumerate() {
+                *line = if i % 2 == 0 { 0x11 } else { 0x44 };
+            }
+        }
+
+        '▒' => {
+            for (i, line) in glyph.iter_mut().enumerate() {
+                *line = if i % 2 == 0 { 0x55 } else { 0xaa };
+            }
+        }
+
+        '▓' => {
+            for (i, line) in glyph.iter_mut().enumerate() {
+                *line = if i % 2 == 0 { 0xee } else { 0xbb };
+            }
+        }
+
+        // upper 1/8
+        '▔' => glyph[0] = 0xff,
+
+        // right 1/8
+        '▕' => glyph.fill(0x80),
+
+        _ => return None,
+    }
+
+    Some(glyph)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glyph;
+
+    fn render(ch: char) -> Vec<String> {
+        glyph(ch)
+            .iter()
+            .map(|line| {
+                (0..8)
+                    .map(|i| if line & (1 << i) != 0 { '#' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ascii() {
+        assert_eq!(
+            render('A'),
+            vec![
+                "..##....", ".####...", "##..##..", "##..##..", "######..", "##..##..", "##..##..",
+                "........"
+            ]
+        );
+    }
+
+    #[test]
+    fn box_drawing() {
+        assert_eq!(
+            render('┼'),
+            vec![
+                "...#....", "...#....", "...#....", "########", "...#....", "...#....", "...#....",
+                "...#...."
+            ]
+        );
+
+        assert_eq!(
+            render('┌'),
+            vec![
+                "........", "........", "........", "...#####", "...#....", "...#....", "...#....",
+                "...#...."
+            ]
+        );
+
+        assert_eq!(
+            render('┛'),
+            vec![
+                "...##...", "...##...", "...##...", "#####...", "#####...", "........", "........",
+                "........"
+            ]
+        );
+    }
+
+    #[test]
+    fn blocks() {
+        assert_eq!(glyph('█'), [0xff; 8]);
+        assert_eq!(glyph('▄'), [0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(glyph('▌'), [0x0f; 8]);
+        assert_eq!(glyph('▏'), [0x01; 8]);
+    }
+}
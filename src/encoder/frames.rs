@@ -0,0 +1,287 @@
+use super::screen::Screen;
+use super::Finish;
+use crate::asciicast::{Event, EventData};
+use crate::tty;
+use avt::Color;
+use serde::Serialize;
+use std::io;
+
+/// When `FramesEncoder` takes snapshots of the screen.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SnapshotEvery {
+    /// Whenever the screen changes, once it settles.
+    #[default]
+    Change,
+    /// At each marker.
+    Marker,
+    /// At regular intervals (in microseconds), starting at 0.
+    Interval(u64),
+}
+
+/// Encodes the recording as JSON lines, each being a snapshot of the
+/// terminal screen: its text with colors and attributes, and the cursor.
+/// Colors are the ones set by the application (palette index or RGB), not
+/// resolved through a theme.
+pub struct FramesEncoder<W: Finish> {
+    writer: W,
+    every: SnapshotEvery,
+    screen: Option<Screen>,
+    next_time: u64,
+}
+
+#[derive(Serialize)]
+struct Snapshot<'a> {
+    time: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    marker: Option<&'a str>,
+    cols: usize,
+    rows: usize,
+    cursor: Cursor,
+    lines: Vec<Vec<Segment>>,
+}
+
+#[derive(Serialize)]
+struct Cursor {
+    col: usize,
+    row: usize,
+    visible: bool,
+}
+
+#[derive(Serialize)]
+struct Segment {
+    col: usize,
+    text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fg: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bg: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "is_false")]
+    bold: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    faint: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    italic: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    underline: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    strikethrough: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    blink: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    inverse: bool,
+}
+
+impl<W: Finish> FramesEncoder<W> {
+    pub fn new(writer: W, every: SnapshotEvery) -> Self {
+        FramesEncoder {
+            writer,
+            every,
+            screen: None,
+            next_time: 0,
+        }
+    }
+
+    fn snapshot(&mut self, time: u64, marker: Option<&str>) -> io::Result<()> {
+        let vt = self.screen.as_ref().unwrap().vt();
+        let (cols, rows) = vt.size();
+        let cursor = vt.cursor();
+
+        let snapshot = Snapshot {
+            time: time as f64 / 1_000_000.0,
+            marker,
+            cols,
+            rows,
+            cursor: Cursor {
+                col: cursor.col,
+                row: cursor.row,
+                visible: cursor.visible,
+            },
+            lines: vt.view().iter().map(segments).collect(),
+        };
+
+        serde_json::to_writer(&mut self.writer, &snapshot)?;
+        self.writer.write_all(b"\n")
+    }
+}
+
+impl<W: Finish> super::Encoder for FramesEncoder<W> {
+    fn start(&mut self, _timestamp: Option<u64>, tty_size: &tty::TtySize) -> io::Result<()> {
+        self.screen = Some(Screen::new(tty_size));
+
+        Ok(())
+    }
+
+    fn event(&mut self, event: &Event) -> io::Result<()> {
+        match self.every {
+            SnapshotEvery::Change => {
+                let screen = self.screen.as_mut().unwrap();
+
+                if screen.changed_before(Some(event.time)) {
+                    let time = screen.time();
+                    self.snapshot(time, None)?;
+                }
+            }
+
+            SnapshotEvery::Interval(interval) => {
+                while self.next_time < event.time {
+                    self.snapshot(self.next_time, None)?;
+                    self.next_time += interval.max(1);
+                }
+            }
+
+            SnapshotEvery::Marker => (),
+        }
+
+        self.screen.as_mut().unwrap().feed(event);
+
+        if let (SnapshotEvery::Marker, EventData::Marker(label)) = (self.every, &event.data) {
+            let time = self.screen.as_ref().unwrap().time();
+            self.snapshot(time, Some(label))?;
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let screen = self.screen.as_mut().unwrap();
+        let end_time = screen.time();
+
+        match self.every {
+            SnapshotEvery::Change => {
+                if screen.changed_before(None) {
+                    self.snapshot(end_time, None)?;
+                }
+            }
+
+            SnapshotEvery::Interval(interval) => {
+                while self.next_time <= end_time {
+                    self.snapshot(self.next_time, None)?;
+                    self.next_time += interval.max(1);
+                }
+            }
+
+            SnapshotEvery::Marker => (),
+        }
+
+        self.writer.finish()
+    }
+}
+
+fn segments(line: &avt::Line) -> Vec<Segment> {
+    let mut col = 0;
+
+    line.segments()
+        .map(|segment| {
+            let text = segment.text();
+            let start = col;
+            col += text.chars().count();
+
+            Segment {
+                col: start,
+                text,
+                fg: segment.foreground().map(color),
+                bg: segment.background().map(color),
+                bold: segment.is_bold(),
+                faint: segment.is_faint(),
+                italic: segment.is_italic(),
+                underline: segment.is_underline(),
+                strikethrough: segment.is_strikethrough(),
+                blink: segment.is_blink(),
+                inverse: segment.is_inverse(),
+            }
+        })
+        .collect()
+}
+
+/// Palette colors are numbers, RGB colors are "#rrggbb" strings.
+fn color(color: Color) -> serde_json::Value {
+    match color {
+        Color::Indexed(i) => i.into(),
+        Color::RGB(c) => format!("#{:02x}{:02x}{:02x}", c.r, c.g, c.b).into(),
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FramesEncoder, SnapshotEvery};
+    use crate::asciicast::Event;
+    use crate::encoder::Encoder;
+    use crate::tty::TtySize;
+    use serde_json::{json, Value};
+
+    fn encode(every: SnapshotEvery) -> Vec<Value> {
+        let mut out: Vec<u8> = Vec::new();
+        let mut enc = FramesEncoder::new(&mut out, every);
+
+        enc.start(None, &TtySize(6, 2)).unwrap();
+        enc.event(&Event::output(0, b"$ ")).unwrap();
+        enc.event(&Event::output(1_000_000, b"\x1b[1;31mls\x1b[0m"))
+            .unwrap();
+        enc.event(&Event::marker(1_500_000, "typed".to_owned()))
+            .unwrap();
+        enc.event(&Event::output(2_500_000, b"\r\n\x1b[38;2;1;2;3mok"))
+            .unwrap();
+        enc.finish().unwrap();
+
+        String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn every_change() {
+        let snapshots = encode(SnapshotEvery::Change);
+
+        let times: Vec<&Value> = snapshots.iter().map(|s| &s["time"]).collect();
+        assert_eq!(times, vec![&json!(0.0), &json!(1.0), &json!(2.5)]);
+
+        assert_eq!(
+            snapshots[1],
+            json!({
+                "time": 1.0,
+                "cols": 6,
+                "rows": 2,
+                "cursor": {"col": 4, "row": 0, "visible": true},
+                "lines": [
+                    [
+                        {"col": 0, "text": "$ "},
+                        {"col": 2, "text": "ls", "fg": 1, "bold": true},
+                        {"col": 4, "text": "  "}
+                    ],
+                    [{"col": 0, "text": "      "}]
+                ]
+            })
+        );
+
+        assert_eq!(
+            snapshots[2]["lines"][1][0],
+            json!({"col": 0, "text": "ok", "fg": "#010203"})
+        );
+    }
+
+    #[test]
+    fn every_marker() {
+        let snapshots = encode(SnapshotEvery::Marker);
+
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0]["time"], json!(1.5));
+        assert_eq!(snapshots[0]["marker"], json!("typed"));
+        assert_eq!(snapshots[0]["lines"][0][1]["text"], json!("ls"));
+    }
+
+    #[test]
+    fn every_interval() {
+        let snapshots = encode(SnapshotEvery::Interval(1_000_000));
+
+        let times: Vec<&Value> = snapshots.iter().map(|s| &s["time"]).collect();
+        assert_eq!(times, vec![&json!(0.0), &json!(1.0), &json!(2.0)]);
+        assert_eq!(snapshots[0]["lines"][0][0]["text"], json!("$     "));
+        assert_eq!(snapshots[1]["lines"][0][1]["text"], json!("ls"));
+    }
+}
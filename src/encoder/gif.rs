@@ -0,0 +1,518 @@
+use super::palette::Palette;
+use super::raster::{self, Image};
+use super::screen::Screen;
+use super::Finish;
+use crate::asciicast::{Event, EventData, Theme};
+use crate::tty;
+use rgb::RGB8;
+use std::collections::HashMap;
+use std::io;
+
+// Frame delays are in centiseconds. Browsers show frames with delay below
+// 2cs for much longer, so such frames get merged with the next one.
+const MIN_DELAY: u64 = 2;
+const LAST_FRAME_DELAY: u64 = 300;
+const MAX_CODE_SIZE: u8 = 12;
+
+/// Renders frames onto a canvas of the largest terminal size in the
+/// recording, which is known only at the end, so events are buffered and
+/// encoded in `finish`.
+pub struct GifEncoder<W: Finish> {
+    writer: W,
+    palette: Palette,
+    screen: Option<Screen>,
+    size: (usize, usize),
+    events: Vec<Event>,
+    pending: Option<Frame>,
+    prev_image: Option<Image>,
+}
+
+struct Frame {
+    time: u64,
+    image: Image,
+}
+
+impl<W: Finish> GifEncoder<W> {
+    pub fn new(writer: W, theme: Option<Theme>) -> Self {
+        GifEncoder {
+            writer,
+            palette: Palette::new(theme.as_ref()),
+            screen: None,
+            size: (0, 0),
+            events: Vec::new(),
+            pending: None,
+            prev_image: None,
+        }
+    }
+
+    /// Takes a snapshot when the event is about to change the screen, then
+    /// feeds it to the screen.
+    fn feed(&mut self, event: &Event) -> io::Result<()> {
+        if self
+            .screen
+            .as_mut()
+            .unwrap()
+            .changed_before(Some(event.time))
+        {
+            self.capture()?;
+        }
+
+        self.screen.as_mut().unwrap().feed(event);
+
+        Ok(())
+    }
+
+    /// Takes a snapshot of the screen.
+    fn capture(&mut self) -> io::Result<()> {
+        let screen = self.screen.as_ref().unwrap();
+        let image = raster::render(screen.vt(), &self.palette, self.size);
+        let time = screen.time();
+
+        match self.pending.take() {
+            Some(pending) if pending.image == image => {
+                self.pending = Some(pending);
+            }
+
+            Some(pending) if delay(pending.time, time) < MIN_DELAY => {
+                self.pending = Some(Frame {
+                    time: pending.time,
+                    image,
+                });
+            }
+
+            Some(pending) => {
+                self.write_frame(pending.image, delay(pending.time, time))?;
+                self.pending = Some(Frame { time, image });
+            }
+
+            None => {
+                self.pending = Some(Frame { time, image });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let width = (self.size.0 * raster::CELL_WIDTH) as u16;
+        let height = (self.size.1 * raster::CELL_HEIGHT) as u16;
+
+        self.writer.write_all(b"GIF89a")?;
+        self.writer.write_all(&width.to_le_bytes())?;
+        self.writer.write_all(&height.to_le_bytes())?;
+        self.writer.write_all(&[0, 0, 0])?;
+
+        // loop forever
+        self.writer
+            .write_all(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00")
+    }
+
+    /// Writes the part of the image that changed since the previous frame.
+    fn write_frame(&mut self, image: Image, delay: u64) -> io::Result<()> {
+        let (x, y, width, height) = match &self.prev_image {
+            Some(prev) => changed_area(prev, &image).unwrap_or((0, 0, 1, 1)),
+            None => (0, 0, image.width, image.height),
+        };
+
+        let pixels = (y..y + height)
+            .flat_map(|row| &image.pixels[row * image.width + x..row * image.width + x + width])
+            .copied();
+
+        let (colors, indices) = quantize(pixels);
+        let table_bits = (colors.len().max(2) - 1).ilog2() as u8 + 1;
+        let delay = delay.min(u16::MAX as u64) as u16;
+
+        // graphic control extension, not disposing the frame
+        self.writer.write_all(&[0x21, 0xf9, 0x04, 0x04])?;
+        self.writer.write_all(&delay.to_le_bytes())?;
+        self.writer.write_all(&[0, 0])?;
+
+        // image descriptor with local color table
+        self.writer.write_all(&[0x2c])?;
+
+        for n in [x, y, width, height] {
+            self.writer.write_all(&(n as u16).to_le_bytes())?;
+        }
+
+        self.writer.write_all(&[0x80 | (table_bits - 1)])?;
+
+        for i in 0..1 << table_bits {
+            let color = colors.get(i).copied().unwrap_or_default();
+            self.writer.write_all(&[color.r, color.g, color.b])?;
+        }
+
+        let min_code_size = table_bits.max(2);
+        self.writer.write_all(&[min_code_size])?;
+
+        for block in lzw_encode(&indices, min_code_size).chunks(255) {
+            self.writer.write_all(&[block.len() as u8])?;
+            self.writer.write_all(block)?;
+        }
+
+        self.writer.write_all(&[0])?;
+        self.prev_image = Some(image);
+
+        Ok(())
+    }
+}
+
+impl<W: Finish> super::Encoder for GifEncoder<W> {
+    fn start(&mut self, _timestamp: Option<u64>, tty_size: &tty::TtySize) -> io::Result<()> {
+        self.size = (tty_size.0 as usize, tty_size.1 as usize);
+        self.screen = Some(Screen::new(tty_size));
+
+        Ok(())
+    }
+
+    fn event(&mut self, event: &Event) -> io::Result<()> {
+        if let EventData::Resize(cols, rows) = event.data {
+            self.size.0 = self.size.0.max(cols as usize);
+            self.size.1 = self.size.1.max(rows as usize);
+        }
+
+        self.events.push(event.clone());
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.write_header()?;
+
+        for event in std::mem::take(&mut self.events) {
+            self.feed(&event)?;
+        }
+
+        if self.screen.as_mut().unwrap().changed_before(None) {
+            self.capture()?;
+        }
+
+        if let Some(pending) = self.pending.take() {
+            self.write_frame(pending.image, LAST_FRAME_DELAY)?;
+        }
+
+        self.writer.write_all(&[0x3b])?;
+        self.writer.finish()
+    }
+}
+
+fn delay(from: u64, to: u64) -> u64 {
+    to / 10_000 - from / 10_000
+}
+
+/// Returns the bounding box (x, y, width, height) of pixels that differ.
+fn changed_area(a: &Image, b: &Image) -> Option<(usize, usize, usize, usize)> {
+    let mut area: Option<(usize, usize, usize, usize)> = None;
+
+    for (i, (pa, pb)) in a.pixels.iter().zip(&b.pixels).enumerate() {
+        if pa != pb {
+            let (x, y) = (i % a.width, i / a.width);
+
+            area = Some(match area {
+                Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+                None => (x, y, x, y),
+            });
+        }
+    }
+
+    area.map(|(x0, y0, x1, y1)| (x0, y0, x1 - x0 + 1, y1 - y0 + 1))
+}
+
+/// Builds a color table for the pixels. When there are more than 256
+/// distinct colors, they're reduced to 3-3-2 bit RGB.
+fn quantize<I: Iterator<Item = RGB8> + Clone>(pixels: I) -> (Vec<RGB8>, Vec<u8>) {
+    let mut colors = Vec::new();
+    let mut lookup = HashMap::new();
+    let mut indices = Vec::new();
+
+    for pixel in pixels.clone() {
+        let index = *lookup.entry(pixel).or_insert_with(|| {
+            colors.push(pixel);
+            colors.len() - 1
+        });
+
+        if index > 255 {
+            let colors = (0..=255)
+                .map(|i: u8| RGB8::new(i & 0xe0, (i << 3) & 0xe0, (i << 6) & 0xc0))
+                .collect();
+
+            let indices = pixels
+                .map(|p| (p.r & 0xe0) | ((p.g & 0xe0) >> 3) | (p.b >> 6))
+                .collect();
+
+            return (colors, indices);
+        }
+
+        indices.push(index as u8);
+    }
+
+    (colors, indices)
+}
+
+fn lzw_encode(data: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear_code = 1u16 << min_code_size;
+    let end_code = clear_code + 1;
+    let mut output = BitWriter::default();
+    let mut codes: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next_code = end_code + 1;
+    let mut code_size = min_code_size + 1;
+
+    output.write(clear_code, code_size);
+
+    let mut iter = data.iter();
+
+    let Some(&first) = iter.next() else {
+        output.write(end_code, code_size);
+
+        return output.finish();
+    };
+
+    let mut prefix = first as u16;
+
+    for &byte in iter {
+        if let Some(&code) = codes.get(&(prefix, byte)) {
+            prefix = code;
+            continue;
+        }
+
+        output.write(prefix, code_size);
+
+        if next_code == 1 << MAX_CODE_SIZE {
+            output.write(clear_code, code_size);
+            codes.clear();
+            next_code = end_code + 1;
+            code_size = min_code_size + 1;
+        } else {
+            codes.insert((prefix, byte), next_code);
+            next_code += 1;
+
+            if next_code > 1 << code_size && code_size < MAX_CODE_SIZE {
+                code_size += 1;
+            }
+        }
+
+        prefix = byte as u16;
+    }
+
+    output.write(prefix, code_size);
+    output.write(end_code, code_size);
+
+    output.finish()
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += size;
+
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GifEncoder;
+    use crate::asciicast::Event;
+    use crate::encoder::Encoder;
+    use crate::tty::TtySize;
+
+    /// Decodes GIF LZW data, as per the spec.
+    fn lzw_decode(data: &[u8], min_code_size: u8) -> Vec<u8> {
+        let clear_code = 1usize << min_code_size;
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut code_size = min_code_size + 1;
+        let mut prev: Option<Vec<u8>> = None;
+        let mut output = Vec::new();
+        let (mut buffer, mut bits, mut pos) = (0u32, 0u8, 0);
+
+        loop {
+            while bits < code_size {
+                buffer |= (data[pos] as u32) << bits;
+                bits += 8;
+                pos += 1;
+            }
+
+            let code = (buffer & ((1 << code_size) - 1)) as usize;
+            buffer >>= code_size;
+            bits -= code_size;
+
+            if code == clear_code {
+                table = (0..clear_code).map(|i| vec![i as u8]).collect();
+                table.push(Vec::new());
+                table.push(Vec::new());
+                code_size = min_code_size + 1;
+                prev = None;
+                continue;
+            }
+
+            if code == clear_code + 1 {
+                return output;
+            }
+
+            let entry = match (&prev, table.get(code)) {
+                (_, Some(entry)) => entry.clone(),
+                (Some(prev), None) => [&prev[..], &prev[..1]].concat(),
+                (None, None) => panic!("invalid code"),
+            };
+
+            if let Some(prev) = prev {
+                if table.len() < 4096 {
+                    table.push([&prev[..], &entry[..1]].concat());
+                }
+            }
+
+            if table.len() == 1 << code_size && code_size < 12 {
+                code_size += 1;
+            }
+
+            output.extend_from_slice(&entry);
+            prev = Some(entry);
+        }
+    }
+
+    /// Returns (delay, x, y, width, height) of each frame.
+    fn frames(gif: &[u8]) -> Vec<(u16, u16, u16, u16, u16)> {
+        let u16_at = |i: usize| u16::from_le_bytes([gif[i], gif[i + 1]]);
+        let mut frames = Vec::new();
+        let mut delay = 0;
+        let mut i = 13;
+
+        loop {
+            match gif[i] {
+                0x21 => {
+                    if gif[i + 1] == 0xf9 {
+                        delay = u16_at(i + 4);
+                    }
+
+                    i += 2;
+
+                    while gif[i] != 0 {
+                        i += gif[i] as usize + 1;
+                    }
+
+                    i += 1;
+                }
+
+                0x2c => {
+                    frames.push((
+                        delay,
+                        u16_at(i + 1),
+                        u16_at(i + 3),
+                        u16_at(i + 5),
+                        u16_at(i + 7),
+                    ));
+                    i += 10 + 3 * (1 << ((gif[i + 9] & 7) + 1)) + 1;
+
+                    while gif[i] != 0 {
+                        i += gif[i] as usize + 1;
+                    }
+
+                    i += 1;
+                }
+
+                0x3b => return frames,
+                b => panic!("unexpected block {b:#x}"),
+            }
+        }
+    }
+
+    #[test]
+    fn lzw() {
+        let data: Vec<u8> = (0..20_000u32)
+            .map(|i| ((i * i) % 3 + i / 1000 % 2) as u8)
+            .collect();
+
+        for min_code_size in [2, 4, 8] {
+            let encoded = super::lzw_encode(&data, min_code_size);
+
+            assert_eq!(lzw_decode(&encoded, min_code_size), data);
+        }
+
+        let noise: Vec<u8> = (0..50_000u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 24) as u8)
+            .collect();
+
+        assert_eq!(lzw_decode(&super::lzw_encode(&noise, 8), 8), noise);
+        assert_eq!(lzw_decode(&super::lzw_encode(&[], 2), 2), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn encoder_impl() {
+        let mut gif: Vec<u8> = Vec::new();
+        let mut enc = GifEncoder::new(&mut gif, None);
+
+        enc.start(None, &TtySize(4, 2)).unwrap();
+        enc.event(&Event::output(0, b"\x1b[?25lab")).unwrap();
+        enc.event(&Event::output(500_000, b"c")).unwrap();
+        enc.event(&Event::output(1_000_000, b"\x1b[1D\x1b[1C"))
+            .unwrap();
+        enc.event(&Event::output(1_005_000, b"d")).unwrap();
+        enc.event(&Event::output(1_010_000, b"\r\nx")).unwrap();
+        enc.event(&Event::marker(3_010_000, "".to_owned())).unwrap();
+        enc.event(&Event::output(4_010_000, b"y")).unwrap();
+        enc.finish().unwrap();
+
+        assert_eq!(&gif[..6], b"GIF89a");
+        assert_eq!(&gif[6..10], &[32, 0, 32, 0]);
+
+        assert_eq!(
+            frames(&gif),
+            vec![
+                // "ab"
+                (50, 0, 0, 32, 32),
+                // "c", the cursor movement after it doesn't change the screen
+                (50, 16, 4, 6, 10),
+                // "d", merged with "x" shown 5ms later, then unchanged for 3s
+                (301, 0, 0, 31, 30),
+                // "y"
+                (300, 8, 20, 6, 12),
+            ]
+        );
+    }
+
+    #[test]
+    fn resize() {
+        let mut gif: Vec<u8> = Vec::new();
+        let mut enc = GifEncoder::new(&mut gif, None);
+
+        enc.start(None, &TtySize(4, 2)).unwrap();
+        enc.event(&Event::output(0, b"\x1b[?25lab")).unwrap();
+        enc.event(&Event::resize(1_000_000, (6, 3))).unwrap();
+        enc.event(&Event::output(2_000_000, b"\x1b[3;6Hz")).unwrap();
+        enc.event(&Event::resize(3_000_000, (2, 1))).unwrap();
+        enc.finish().unwrap();
+
+        // canvas of the largest size
+        assert_eq!(&gif[6..10], &[48, 0, 48, 0]);
+
+        let frames = frames(&gif);
+
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0], (200, 0, 0, 48, 48));
+
+        // "z" in the bottom right cell, beyond the initial size
+        let (_, x, y, _, _) = frames[1];
+        assert!(x >= 40 && y >= 32);
+
+        // shrinking clears the part beyond the new size, "z" included
+        assert_eq!(frames[2], (300, 0, 0, 46, 46));
+    }
+}
@@ -0,0 +1,397 @@
+use super::palette::Palette;
+use super::screen::{self, Run, Screen, Style};
+use super::Finish;
+use crate::asciicast::{Event, Theme};
+use crate::tty;
+use rgb::RGB8;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io;
+
+const TEMPLATE: &str = include_str!("player.html");
+const FONT_FAMILY: &str = "'SFMono-Regular', Menlo, Consolas, 'Liberation Mono', monospace";
+
+/// Encodes the recording as a standalone HTML page, with screen states
+/// embedded as JSON and a small inline player. It doesn't load anything
+/// from the network.
+pub struct HtmlEncoder<W: Finish> {
+    writer: W,
+    palette: Palette,
+    title: Option<String>,
+    screen: Option<Screen>,
+    size: (usize, usize),
+    recording: Recording,
+    state_ids: HashMap<Vec<Vec<(String, usize)>>, usize>,
+    style_ids: HashMap<Style, usize>,
+}
+
+#[derive(Default, Serialize)]
+struct Recording {
+    duration: f64,
+    styles: Vec<String>,
+    states: Vec<Vec<Vec<(String, usize)>>>,
+    timeline: Vec<(f64, usize)>,
+}
+
+impl<W: Finish> HtmlEncoder<W> {
+    pub fn new(writer: W, theme: Option<Theme>, title: Option<String>) -> Self {
+        HtmlEncoder {
+            writer,
+            palette: Palette::new(theme.as_ref()),
+            title,
+            screen: None,
+            size: (0, 0),
+            recording: Recording::default(),
+            state_ids: HashMap::new(),
+            style_ids: HashMap::new(),
+        }
+    }
+
+    fn capture(&mut self) {
+        let screen = self.screen.as_ref().unwrap();
+        let (cols, rows) = screen.vt().size();
+        let time = seconds(screen.time());
+        let lines = screen::runs(screen.vt(), &self.palette);
+        self.size = (self.size.0.max(cols), self.size.1.max(rows));
+
+        let state: Vec<Vec<(String, usize)>> =
+            lines.into_iter().map(|runs| self.line(runs)).collect();
+
+        let recording = &mut self.recording;
+
+        let id = *self.state_ids.entry(state).or_insert_with_key(|state| {
+            recording.states.push(state.clone());
+            recording.states.len() - 1
+        });
+
+        match recording.timeline.last() {
+            Some((_, last_id)) if *last_id == id => (),
+            _ => recording.timeline.push((time, id)),
+        }
+    }
+
+    fn line(&mut self, mut runs: Vec<Run>) -> Vec<(String, usize)> {
+        trim_end(&mut runs, &self.palette);
+
+        runs.into_iter()
+            .map(|run| {
+                let next_id = self.style_ids.len();
+
+                let id = *self.style_ids.entry(run.style).or_insert_with_key(|style| {
+                    self.recording.styles.push(css(style, &self.palette));
+                    next_id
+                });
+
+                (run.text, id)
+            })
+            .collect()
+    }
+
+    fn write_html(&mut self) -> io::Result<()> {
+        let recording = serde_json::to_string(&self.recording)?.replace("</", "<\\/");
+        let title = escape(self.title.as_deref().unwrap_or("asciinema recording"));
+        let fg = color(self.palette.fg);
+        let bg = color(self.palette.bg);
+        let cols = self.size.0.to_string();
+        let rows = self.size.1.to_string();
+
+        let html = fill(
+            TEMPLATE,
+            &[
+                ("TITLE", &title),
+                ("FG", &fg),
+                ("BG", &bg),
+                ("COLS", &cols),
+                ("ROWS", &rows),
+                ("RECORDING", &recording),
+            ],
+        );
+
+        self.writer.write_all(html.as_bytes())
+    }
+}
+
+impl<W: Finish> super::Encoder for HtmlEncoder<W> {
+    fn start(&mut self, _timestamp: Option<u64>, tty_size: &tty::TtySize) -> io::Result<()> {
+        self.size = (tty_size.0 as usize, tty_size.1 as usize);
+        self.screen = Some(Screen::new(tty_size));
+
+        Ok(())
+    }
+
+    fn event(&mut self, event: &Event) -> io::Result<()> {
+        if self
+            .screen
+            .as_mut()
+            .unwrap()
+            .changed_before(Some(event.time))
+        {
+            self.capture();
+        }
+
+        self.screen.as_mut().unwrap().feed(event);
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if self.screen.as_mut().unwrap().changed_before(None) {
+            self.capture();
+        }
+
+        self.recording.duration = seconds(self.screen.as_ref().unwrap().time());
+        self.write_html()?;
+        self.writer.finish()
+    }
+}
+
+/// Renders the terminal screen at the end of the (possibly cut) recording
+/// as a static HTML page with a single styled `<pre>`.
+pub struct HtmlSnapshotEncoder<W: Finish> {
+    writer: W,
+    palette: Palette,
+    title: Option<String>,
+    screen: Option<Screen>,
+}
+
+impl<W: Finish> HtmlSnapshotEncoder<W> {
+    pub fn new(writer: W, theme: Option<Theme>, title: Option<String>) -> Self {
+        HtmlSnapshotEncoder {
+            writer,
+            palette: Palette::new(theme.as_ref()),
+            title,
+            screen: None,
+        }
+    }
+}
+
+impl<W: Finish> super::Encoder for HtmlSnapshotEncoder<W> {
+    fn start(&mut self, _timestamp: Option<u64>, tty_size: &tty::TtySize) -> io::Result<()> {
+        self.screen = Some(Screen::new(tty_size));
+
+        Ok(())
+    }
+
+    fn event(&mut self, event: &Event) -> io::Result<()> {
+        self.screen.as_mut().unwrap().feed(event);
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let screen = self.screen.take().unwrap();
+        let title = escape(self.title.as_deref().unwrap_or("asciinema snapshot"));
+
+        let lines: Vec<String> = screen::runs(screen.vt(), &self.palette)
+            .into_iter()
+            .map(|mut runs| {
+                trim_end(&mut runs, &self.palette);
+
+                runs.iter()
+                    .map(|run| match css(&run.style, &self.palette) {
+                        css if css.is_empty() => escape(&run.text),
+                        css => format!(r#"<span style="{css}">{}</span>"#, escape(&run.text)),
+                    })
+                    .collect()
+            })
+            .collect();
+
+        writeln!(self.writer, "<!DOCTYPE html>")?;
+        writeln!(self.writer, r#"<html lang="en">"#)?;
+        writeln!(self.writer, r#"<head>"#)?;
+        writeln!(self.writer, r#"<meta charset="utf-8">"#)?;
+        writeln!(self.writer, "<title>{title}</title>")?;
+        writeln!(self.writer, "</head>")?;
+        writeln!(self.writer, "<body>")?;
+
+        writeln!(
+            self.writer,
+            r#"<pre style="display: inline-block; margin: 0; padding: 12px; background: {}; color: {}; font-family: {FONT_FAMILY}; font-size: 14px; line-height: 1.25;">{}</pre>"#,
+            color(self.palette.bg),
+            color(self.palette.fg),
+            lines.join("\n")
+        )?;
+
+        writeln!(self.writer, "</body>")?;
+        writeln!(self.writer, "</html>")?;
+
+        self.writer.finish()
+    }
+}
+
+/// Drops trailing blanks which render the same as the page background.
+fn trim_end(runs: &mut Vec<Run>, palette: &Palette) {
+    while let Some(run) = runs.last_mut() {
+        if run.style.bg != palette.bg || run.style.underline || run.style.strikethrough {
+            break;
+        }
+
+        run.text.truncate(run.text.trim_end_matches(' ').len());
+
+        if !run.text.is_empty() {
+            break;
+        }
+
+        runs.pop();
+    }
+}
+
+fn css(style: &Style, palette: &Palette) -> String {
+    let mut css = Vec::new();
+
+    if style.fg != palette.fg {
+        css.push(format!("color:{}", color(style.fg)));
+    }
+
+    if style.bg != palette.bg {
+        css.push(format!("background:{}", color(style.bg)));
+    }
+
+    if style.bold {
+        css.push("font-weight:bold".to_owned());
+    }
+
+    if style.italic {
+        css.push("font-style:italic".to_owned());
+    }
+
+    match (style.underline, style.strikethrough) {
+        (true, true) => css.push("text-decoration:underline line-through".to_owned()),
+        (true, false) => css.push("text-decoration:underline".to_owned()),
+        (false, true) => css.push("text-decoration:line-through".to_owned()),
+        (false, false) => (),
+    }
+
+    css.join(";")
+}
+
+fn color(color: RGB8) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+fn seconds(time: u64) -> f64 {
+    (time / 1000) as f64 / 1000.0
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Substitutes `{{NAME}}` placeholders in a single pass.
+fn fill(template: &str, vars: &[(&str, &str)]) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let var = vars.iter().find(|(name, _)| {
+            rest[2..].starts_with(name) && rest[2 + name.len()..].starts_with("}}")
+        });
+
+        match var {
+            Some((name, value)) => {
+                output.push_str(value);
+                rest = &rest[name.len() + 4..];
+            }
+
+            None => {
+                output.push_str("{{");
+                rest = &rest[2..];
+            }
+        }
+    }
+
+    output.push_str(rest);
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HtmlEncoder, HtmlSnapshotEncoder};
+    use crate::asciicast::Event;
+    use crate::encoder::Encoder;
+    use crate::tty::TtySize;
+
+    #[test]
+    fn encoder_impl() {
+        let mut out: Vec<u8> = Vec::new();
+        let mut enc = HtmlEncoder::new(&mut out, None, Some("<demo>".to_owned()));
+
+        enc.start(None, &TtySize(30, 2)).unwrap();
+        enc.event(&Event::output(0, b"\x1b[?25l$ ")).unwrap();
+        enc.event(&Event::output(
+            500_000,
+            b"\x1b[1;31m</script>{{TITLE}}\x1b[0m  ",
+        ))
+        .unwrap();
+        enc.event(&Event::output(1_500_000, b"\r\x1b[K$ ")).unwrap();
+        enc.finish().unwrap();
+
+        let html = String::from_utf8(out).unwrap();
+
+        assert!(html.contains("<title>&lt;demo&gt;</title>"));
+        assert!(html.contains("width: 30ch; height: calc(2 * 1.25em)"));
+        assert!(!html.contains("http"));
+
+        let json = html
+            .split(r#"<script type="application/json" id="recording">"#)
+            .nth(1)
+            .unwrap()
+            .split("</script>")
+            .next()
+            .unwrap();
+
+        let recording: serde_json::Value = serde_json::from_str(json).unwrap();
+
+        assert_eq!(recording["duration"], 1.5);
+        assert_eq!(
+            recording["styles"],
+            serde_json::json!(["", "color:#dd3c69;font-weight:bold"])
+        );
+
+        assert_eq!(
+            recording["states"],
+            serde_json::json!([
+                [[["$", 0]], []],
+                [[["$ ", 0], ["</script>{{TITLE}}", 1]], []]
+            ])
+        );
+
+        assert_eq!(
+            recording["timeline"],
+            serde_json::json!([[0.0, 0], [0.5, 1], [1.5, 0]])
+        );
+    }
+
+    #[test]
+    fn snapshot_encoder_impl() {
+        let mut out: Vec<u8> = Vec::new();
+        let mut enc = HtmlSnapshotEncoder::new(&mut out, None, None);
+
+        enc.start(None, &TtySize(12, 3)).unwrap();
+        enc.event(&Event::output(
+            0,
+            b"\x1b[?25l$ \x1b[1;4;32mok\x1b[0m <x>\r\n",
+        ))
+        .unwrap();
+        enc.event(&Event::output(1, b"\x1b[41m  \x1b[0m")).unwrap();
+        enc.finish().unwrap();
+
+        let html = String::from_utf8(out).unwrap();
+
+        assert!(html.contains("<title>asciinema snapshot</title>"));
+        assert!(html.contains("background: #121314; color: #cccccc;"));
+        assert!(html.contains(concat!(
+            r#"1.25;">$ <span style="color:#4ebf22;font-weight:bold;text-decoration:underline">ok</span> &lt;x&gt;"#,
+            "\n",
+            r#"<span style="background:#dd3c69">  </span>"#,
+            "\n</pre>"
+        )));
+    }
+}
@@ -0,0 +1,336 @@
+use super::Finish;
+use crate::asciicast::{Event, EventData};
+use crate::tty;
+use std::io;
+
+const PASTE_START: &str = "\x1b[200~";
+const PASTE_END: &str = "\x1b[201~";
+
+/// Writes input events as a timestamped keystroke log, one line per event.
+/// Printable characters are written as is (with `<` as `<lt>`), control
+/// bytes and escape sequences as key names like `<Ctrl-C>`, `<Up>` or
+/// `<Alt-b>`, and bracketed paste is wrapped in `<Paste>...</Paste>`.
+/// Terminal replies to queries sent by programs (e.g. cursor position
+/// reports) aren't keystrokes, so they're left out.
+pub struct KeystrokesEncoder<W: Finish> {
+    writer: W,
+    paste: bool,
+}
+
+impl<W: Finish> KeystrokesEncoder<W> {
+    pub fn new(writer: W) -> Self {
+        KeystrokesEncoder {
+            writer,
+            paste: false,
+        }
+    }
+
+    fn decode(&mut self, data: &str) -> String {
+        let mut keys = String::new();
+        let mut rest = data;
+
+        while let Some(c) = rest.chars().next() {
+            if self.paste {
+                if let Some(r) = rest.strip_prefix(PASTE_END) {
+                    keys.push_str("</Paste>");
+                    self.paste = false;
+                    rest = r;
+                } else {
+                    push_key(&mut keys, c);
+                    rest = &rest[c.len_utf8()..];
+                }
+            } else if let Some(r) = rest.strip_prefix(PASTE_START) {
+                keys.push_str("<Paste>");
+                self.paste = true;
+                rest = r;
+            } else if let Some(len) = rest.strip_prefix('\x1b').and_then(reply_len) {
+                rest = &rest[1 + len..];
+            } else if c == '\x1b' && rest.len() > 1 {
+                let (name, len) = escape_sequence(&rest[1..]);
+                keys.push_str(&format!("<{name}>"));
+                rest = &rest[1 + len..];
+            } else {
+                push_key(&mut keys, c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+
+        keys
+    }
+}
+
+impl<W: Finish> super::Encoder for KeystrokesEncoder<W> {
+    fn start(&mut self, _timestamp: Option<u64>, _tty_size: &tty::TtySize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn event(&mut self, event: &Event) -> io::Result<()> {
+        if let EventData::Input(data) = &event.data {
+            let keys = self.decode(data);
+
+            if keys.is_empty() {
+                return Ok(());
+            }
+
+            writeln!(
+                self.writer,
+                "{:>10.3} {}",
+                event.time as f64 / 1_000_000.0,
+                keys
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.finish()
+    }
+}
+
+fn push_key(keys: &mut String, c: char) {
+    match key_name(c) {
+        Some(name) => {
+            keys.push('<');
+            keys.push_str(&name);
+            keys.push('>');
+        }
+
+        None => keys.push(c),
+    }
+}
+
+/// Returns the name of a key sending the character, for non-printable ones
+/// (and `<`).
+fn key_name(c: char) -> Option<String> {
+    match c {
+        '\r' => Some("Enter".to_owned()),
+        '\t' => Some("Tab".to_owned()),
+        '\x7f' => Some("Backspace".to_owned()),
+        '\x1b' => Some("Esc".to_owned()),
+        '\0' => Some("Ctrl-Space".to_owned()),
+        '\x01'..='\x1a' => Some(format!("Ctrl-{}", (b'A' + c as u8 - 1) as char)),
+        '\x1c'..='\x1f' => Some(format!("Ctrl-{}", (b'\\' + c as u8 - 0x1c) as char)),
+        '<' => Some("lt".to_owned()),
+        c if c.is_control() => Some(format!("U+{:04X}", c as u32)),
+        _ => None,
+    }
+}
+
+/// Decodes an escape sequence (what follows ESC) into a key name, returning
+/// it along with the number of bytes it took.
+fn escape_sequence(seq: &str) -> (String, usize) {
+    if let Some(csi) = seq.strip_prefix('[') {
+        if let Some((name, len)) = csi_sequence(csi) {
+            return (name, len + 1);
+        }
+    } else if let Some(ss3) = seq.strip_prefix('O') {
+        if let Some(name) = ss3.chars().next().and_then(|c| cursor_key(c, 1)) {
+            return (name, 2);
+        }
+    }
+
+    let c = seq.chars().next().unwrap();
+
+    match key_name(c) {
+        Some(name) => (format!("Alt-{name}"), c.len_utf8()),
+        None => (format!("Alt-{c}"), c.len_utf8()),
+    }
+}
+
+/// Returns the length of a terminal reply (what follows ESC), if the
+/// sequence is one: a cursor position report, device attributes, a mode
+/// report, or an OSC/DCS string (e.g. a color query reply). A position
+/// report for the first row can't be told from Ctrl/Alt/Shift-F3, so it's
+/// left to be decoded as a key.
+fn reply_len(seq: &str) -> Option<usize> {
+    if let Some(csi) = seq.strip_prefix('[') {
+        let end = csi.find(|c: char| ('\x40'..='\x7e').contains(&c))?;
+        let params = &csi[..end];
+
+        let reply = match &csi[end..end + 1] {
+            "R" => params.split_once(';').is_some_and(|(row, col)| {
+                row != "1" && [row, col].iter().all(|n| n.parse::<u16>().is_ok())
+            }),
+
+            "c" => params.starts_with(['?', '>']),
+            "y" => params.ends_with('$'),
+            _ => false,
+        };
+
+        return reply.then_some(end + 2);
+    }
+
+    if seq.starts_with([']', 'P']) {
+        let (end, terminator) = [
+            seq.find('\x07').map(|i| (i, 1)),
+            seq.find("\x1b\\").map(|i| (i, 2)),
+        ]
+        .into_iter()
+        .flatten()
+        .min()?;
+
+        return Some(end + terminator);
+    }
+
+    None
+}
+
+fn csi_sequence(csi: &str) -> Option<(String, usize)> {
+    let end = csi.find(|c: char| ('\x40'..='\x7e').contains(&c))?;
+    let params = &csi[..end];
+    let final_byte = csi[end..].chars().next().unwrap();
+    let len = end + 1;
+
+    if !params.chars().all(|c| ('\x20'..='\x3f').contains(&c)) {
+        return None;
+    }
+
+    // X10 mouse reports carry 3 more bytes
+    if params.is_empty() && final_byte == 'M' {
+        let extra: usize = csi[len..].chars().take(3).map(char::len_utf8).sum();
+        return Some(("Mouse".to_owned(), len + extra));
+    }
+
+    if params.starts_with('<') && (final_byte == 'M' || final_byte == 'm') {
+        return Some(("Mouse".to_owned(), len));
+    }
+
+    let mut parts = params.split(';');
+    let code = parts.next().unwrap_or_default();
+    let modifiers = parts.next().and_then(|m| m.parse().ok()).unwrap_or(1);
+
+    let name = match (code, final_byte) {
+        ("", 'Z') => Some("Shift-Tab".to_owned()),
+        ("", 'I') => Some("FocusIn".to_owned()),
+        ("", 'O') => Some("FocusOut".to_owned()),
+        ("" | "1", c) => cursor_key(c, modifiers),
+        (code, '~') => tilde_key(code, modifiers),
+        _ => None,
+    };
+
+    Some((
+        name.unwrap_or_else(|| format!("CSI {params}{final_byte}")),
+        len,
+    ))
+}
+
+fn cursor_key(c: char, modifiers: u8) -> Option<String> {
+    let name = match c {
+        'A' => "Up",
+        'B' => "Down",
+        'C' => "Right",
+        'D' => "Left",
+        'H' => "Home",
+        'F' => "End",
+        'P' => "F1",
+        'Q' => "F2",
+        'R' => "F3",
+        'S' => "F4",
+        _ => return None,
+    };
+
+    Some(with_modifiers(name, modifiers))
+}
+
+fn tilde_key(code: &str, modifiers: u8) -> Option<String> {
+    let name = match code {
+        "1" | "7" => "Home",
+        "2" => "Insert",
+        "3" => "Delete",
+        "4" | "8" => "End",
+        "5" => "PageUp",
+        "6" => "PageDown",
+        "11" => "F1",
+        "12" => "F2",
+        "13" => "F3",
+        "14" => "F4",
+        "15" => "F5",
+        "17" => "F6",
+        "18" => "F7",
+        "19" => "F8",
+        "20" => "F9",
+        "21" => "F10",
+        "23" => "F11",
+        "24" => "F12",
+        _ => return None,
+    };
+
+    Some(with_modifiers(name, modifiers))
+}
+
+/// Prefixes the key name with modifiers encoded xterm style (1 + bits of
+/// Shift=1, Alt=2, Ctrl=4).
+fn with_modifiers(name: &str, modifiers: u8) -> String {
+    let bits = modifiers.saturating_sub(1);
+    let mut prefix = String::new();
+
+    if bits & 4 != 0 {
+        prefix.push_str("Ctrl-");
+    }
+
+    if bits & 2 != 0 {
+        prefix.push_str("Alt-");
+    }
+
+    if bits & 1 != 0 {
+        prefix.push_str("Shift-");
+    }
+
+    prefix + name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KeystrokesEncoder;
+    use crate::asciicast::Event;
+    use crate::encoder::Encoder;
+    use crate::tty::TtySize;
+
+    #[test]
+    fn encoder_impl() {
+        let mut out: Vec<u8> = Vec::new();
+        let mut enc = KeystrokesEncoder::new(&mut out);
+
+        enc.start(None, &TtySize(80, 24)).unwrap();
+        enc.event(&Event::output(0, b"$ ")).unwrap();
+        enc.event(&Event::input(500_000, b"ls -l <x\r")).unwrap();
+        enc.event(&Event::input(
+            1_250_000,
+            b"\x1b[A\x1b[1;5D\x1bOA\x1b[3~\x1b[15;2~",
+        ))
+        .unwrap();
+        enc.event(&Event::input(2_000_000, b"\x03\x1bb\x1b\x7f\x1b\t\x1b"))
+            .unwrap();
+        enc.event(&Event::input(3_000_000, b"\x1b[200~echo \x1b[A\r"))
+            .unwrap();
+        enc.event(&Event::input(3_000_100, b"hi\x1b[201~\r"))
+            .unwrap();
+        enc.event(&Event::input(4_000_000, b"\x1b[2;2R\x1b[>0;95;0c"))
+            .unwrap();
+        enc.event(&Event::input(
+            5_000_000,
+            b"a\x1b[12;40Rb\x1b]11;rgb:0000/0000/0000\x07\x1b[?1;2c\x1b[?2004;1$y\x1b[1;5R",
+        ))
+        .unwrap();
+        enc.event(&Event::input(
+            65_000_000,
+            b"\x1b[<0;3;4M\x1b[Mabc\x1b[9x\x1c",
+        ))
+        .unwrap();
+        enc.finish().unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                "     0.500 ls -l <lt>x<Enter>\n",
+                "     1.250 <Up><Ctrl-Left><Up><Delete><Shift-F5>\n",
+                "     2.000 <Ctrl-C><Alt-b><Alt-Backspace><Alt-Tab><Esc>\n",
+                "     3.000 <Paste>echo <Esc>[A<Enter>\n",
+                "     3.000 hi</Paste><Enter>\n",
+                "     5.000 ab<Ctrl-F3>\n",
+                "    65.000 <Mouse><Mouse><CSI 9x><Ctrl-\\>\n",
+            )
+        );
+    }
+}
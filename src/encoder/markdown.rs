@@ -0,0 +1,487 @@
+use super::Finish;
+use crate::asciicast::{Event, EventData};
+use crate::tty;
+use std::collections::HashSet;
+use std::io;
+
+const PROMPT_CHARS: [char; 5] = ['$', '#', '%', '>', '❯'];
+const MAX_PROMPT_LEN: usize = 64;
+const RIGHT_PROMPT_GAP: &str = "   ";
+
+/// Writes a Markdown transcript of the session: typed commands and their
+/// output as fenced code blocks, with markers as headings.
+///
+/// Commands are found with input events (a line the Enter key was pressed
+/// on), or, for recordings without input, by looking for shell prompts in
+/// the final terminal contents.
+pub struct MarkdownEncoder<W: Finish> {
+    writer: W,
+    title: Option<String>,
+    vt: Option<avt::Vt>,
+    entries: Vec<Entry>,
+    command_start: Option<(usize, usize)>,
+}
+
+/// Positions are rows in the terminal's scrollback plus screen.
+#[derive(Debug)]
+enum Entry {
+    Marker {
+        row: usize,
+        label: String,
+    },
+    Command {
+        row: usize,
+        col: usize,
+        end_row: usize,
+    },
+}
+
+impl Entry {
+    fn row(&self) -> usize {
+        match self {
+            Entry::Marker { row, .. } | Entry::Command { row, .. } => *row,
+        }
+    }
+}
+
+impl<W: Finish> MarkdownEncoder<W> {
+    pub fn new(writer: W, title: Option<String>) -> Self {
+        MarkdownEncoder {
+            writer,
+            title,
+            vt: None,
+            entries: Vec::new(),
+            command_start: None,
+        }
+    }
+
+    fn cursor_position(&self) -> (usize, usize) {
+        let vt = self.vt.as_ref().unwrap();
+        let cursor = vt.cursor();
+        let scrollback = vt.lines().len() - vt.size().1;
+
+        (scrollback + cursor.row, cursor.col)
+    }
+
+    fn input(&mut self, data: &str) {
+        if data.contains(['\x03', '\x04']) {
+            self.command_start = None;
+            return;
+        }
+
+        let (row, col) = self.cursor_position();
+        let (start_row, start_col) = *self.command_start.get_or_insert((row, col));
+
+        if data.contains(['\r', '\n']) {
+            self.entries.push(Entry::Command {
+                row: start_row,
+                col: start_col,
+                end_row: row.max(start_row),
+            });
+
+            self.command_start = None;
+        }
+    }
+
+    fn write_block(&mut self, lang: &str, lines: &[String]) -> io::Result<()> {
+        let fence = fence(lines);
+
+        writeln!(self.writer, "{fence}{lang}")?;
+
+        for line in lines {
+            writeln!(self.writer, "{line}")?;
+        }
+
+        writeln!(self.writer, "{fence}\n")
+    }
+
+    /// Writes the output rows, without bare `prompts` (ones no command was
+    /// entered at).
+    fn write_output(&mut self, rows: &[String], prompts: &HashSet<String>) -> io::Result<()> {
+        let lines: Vec<String> = join_wrapped(rows)
+            .into_iter()
+            .filter(|line| !prompts.contains(line.trim()))
+            .collect();
+
+        let start = lines.iter().position(|l| !l.is_empty());
+        let end = lines.iter().rposition(|l| !l.is_empty());
+
+        match (start, end) {
+            (Some(start), Some(end)) => self.write_block("", &lines[start..=end]),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<W: Finish> super::Encoder for MarkdownEncoder<W> {
+    fn start(&mut self, _timestamp: Option<u64>, tty_size: &tty::TtySize) -> io::Result<()> {
+        self.vt = Some(
+            avt::Vt::builder()
+                .size(tty_size.0 as usize, tty_size.1 as usize)
+                .resizable(true)
+                .build(),
+        );
+
+        Ok(())
+    }
+
+    fn event(&mut self, event: &Event) -> io::Result<()> {
+        let vt = self.vt.as_mut().unwrap();
+
+        match &event.data {
+            EventData::Output(data) => {
+                vt.feed_str(data);
+            }
+
+            EventData::Bytes(data) => {
+                vt.feed_str(&String::from_utf8_lossy(data));
+            }
+
+            EventData::Resize(cols, rows) => {
+                vt.feed_str(&format!("\x1b[8;{rows};{cols}t"));
+            }
+
+            EventData::Input(data) => {
+                self.input(data);
+            }
+
+            EventData::Marker(label) => {
+                let (row, _) = self.cursor_position();
+
+                self.entries.push(Entry::Marker {
+                    row,
+                    label: label.clone(),
+                });
+            }
+
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let vt = self.vt.take().unwrap();
+        let rows: Vec<String> = vt
+            .lines()
+            .iter()
+            .map(|line| without_right_prompt(line.text()))
+            .collect();
+        let mut entries = std::mem::take(&mut self.entries);
+
+        if !entries.iter().any(|e| matches!(e, Entry::Command { .. })) {
+            entries.extend(detect_commands(&rows));
+        }
+
+        // markers go before commands typed on the same row
+        entries.sort_by_key(|e| (e.row(), matches!(e, Entry::Command { .. })));
+
+        let prompts: HashSet<String> = entries
+            .iter()
+            .filter_map(|entry| match entry {
+                Entry::Command { row, col, .. } => rows.get(*row).map(|text| {
+                    let prompt: String = text.chars().take(*col).collect();
+
+                    prompt.trim().to_owned()
+                }),
+
+                Entry::Marker { .. } => None,
+            })
+            .filter(|prompt| !prompt.is_empty())
+            .collect();
+
+        if let Some(title) = self.title.take() {
+            writeln!(self.writer, "# {title}\n")?;
+        }
+
+        let mut pos = 0;
+
+        for entry in entries {
+            let row = entry.row().min(rows.len());
+            self.write_output(&rows[pos.min(row)..row], &prompts)?;
+            pos = pos.max(row);
+
+            match entry {
+                Entry::Marker { label, .. } => {
+                    writeln!(self.writer, "## {label}\n")?;
+                }
+
+                Entry::Command { row, col, end_row } => {
+                    let end_row = end_row.min(rows.len().saturating_sub(1));
+
+                    if row > end_row {
+                        continue;
+                    }
+
+                    let text: String = rows[row..=end_row].concat().chars().skip(col).collect();
+                    let command = text.trim();
+
+                    if !command.is_empty() {
+                        self.write_block("sh", &[command.to_owned()])?;
+                    }
+
+                    pos = pos.max(end_row + 1);
+                }
+            }
+        }
+
+        self.write_output(&rows[pos.min(rows.len())..], &prompts)?;
+
+        self.writer.finish()
+    }
+}
+
+/// Finds (possibly wrapped) rows starting with a shell prompt followed by
+/// a command. To tell prompts from output looking like one (e.g. `# Title`
+/// or `50% done`), a prompt has to be followed by a command more than once,
+/// or be the first one found.
+fn detect_commands(rows: &[String]) -> Vec<Entry> {
+    let mut candidates = Vec::new();
+    let mut row = 0;
+
+    while row < rows.len() {
+        let mut end_row = row;
+
+        while is_wrapped(&rows[end_row]) && end_row + 1 < rows.len() {
+            end_row += 1;
+        }
+
+        let text = rows[row..=end_row].concat();
+
+        if let Some((prompt, command)) = split_prompt(text.trim_end()) {
+            candidates.push((row, end_row, prompt.to_owned(), !command.is_empty()));
+        }
+
+        row = end_row + 1;
+    }
+
+    let first = candidates.first().map(|(_, _, prompt, _)| prompt.clone());
+    let count = |prompt: &String| {
+        candidates
+            .iter()
+            .filter(|(_, _, p, has_command)| *has_command && p == prompt)
+            .count()
+    };
+
+    candidates
+        .iter()
+        .filter(|(_, _, prompt, has_command)| {
+            *has_command && (first.as_ref() == Some(prompt) || count(prompt) > 1)
+        })
+        .map(|(row, end_row, prompt, _)| Entry::Command {
+            row: *row,
+            col: prompt.chars().count(),
+            end_row: *end_row,
+        })
+        .collect()
+}
+
+/// Joins rows with the ones they most likely wrap into, trimming the
+/// resulting lines.
+fn join_wrapped(rows: &[String]) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+
+    for (i, row) in rows.iter().enumerate() {
+        line.push_str(row);
+
+        if !is_wrapped(row) || i + 1 == rows.len() {
+            lines.push(line.trim_end().to_owned());
+            line.clear();
+        }
+    }
+
+    lines
+}
+
+/// A row filled up to the last column is assumed to continue on the next
+/// one (avt doesn't expose its wrapping flag).
+fn is_wrapped(row: &str) -> bool {
+    row.ends_with(|c: char| c != ' ')
+}
+
+/// Blanks out the right prompt a prompt row ends with, if any: text reaching
+/// the last column, separated from the rest of the row by a run of spaces
+/// (e.g. fish's `~/src (main)`). Otherwise the row would look wrapped, and
+/// the right prompt would end up in the command.
+fn without_right_prompt(row: String) -> String {
+    if !is_wrapped(&row) || split_prompt(&row).is_none() {
+        return row;
+    }
+
+    match row.rfind(RIGHT_PROMPT_GAP) {
+        Some(i) => {
+            let (left, right) = row.split_at(i);
+
+            format!("{left}{}", " ".repeat(right.chars().count()))
+        }
+
+        None => row,
+    }
+}
+
+/// Splits a line looking like a shell prompt into the prompt and the rest:
+/// an optional parenthesized group (e.g. `(venv) `), then a single short word
+/// ending with one of `$ # % > ❯` (e.g. `user@host:~/src$`), then a space or
+/// the end of the line.
+fn split_prompt(line: &str) -> Option<(&str, &str)> {
+    let mut start = 0;
+
+    if line.starts_with('(') {
+        start = line.find(") ")? + 2;
+    }
+
+    let word_len = line[start..]
+        .find(char::is_whitespace)
+        .unwrap_or(line.len() - start);
+
+    let end = start + word_len;
+    let word = &line[start..end];
+
+    if word.is_empty() || word.chars().count() > MAX_PROMPT_LEN || !word.ends_with(PROMPT_CHARS) {
+        return None;
+    }
+
+    Some((&line[..end], line[end..].trim()))
+}
+
+/// Returns a backtick fence longer than any backtick run in the lines.
+fn fence(lines: &[String]) -> String {
+    let longest = lines
+        .iter()
+        .flat_map(|line| line.split(|c| c != '`'))
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+
+    "`".repeat(longest.max(2) + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MarkdownEncoder;
+    use crate::asciicast::Event;
+    use crate::encoder::Encoder;
+    use crate::tty::TtySize;
+
+    fn encode(events: &[Event]) -> String {
+        let mut out: Vec<u8> = Vec::new();
+        let mut enc = MarkdownEncoder::new(&mut out, Some("Demo".to_owned()));
+
+        enc.start(None, &TtySize(20, 3)).unwrap();
+
+        for event in events {
+            enc.event(event).unwrap();
+        }
+
+        enc.finish().unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn with_input() {
+        let md = encode(&[
+            Event::output(0, b"~ % "),
+            Event::marker(1, "Listing".to_owned()),
+            Event::input(2, b"l"),
+            Event::output(3, b"l"),
+            Event::input(4, b"s\r"),
+            Event::output(5, b"s\r\nfoo\r\n```\r\n~ % "),
+            Event::input(6, b"exit\r"),
+            Event::output(7, b"exit\r\n"),
+        ]);
+
+        assert_eq!(
+            md,
+            concat!(
+                "# Demo\n\n",
+                "## Listing\n\n",
+                "```sh\nls\n```\n\n",
+                "````\nfoo\n```\n````\n\n",
+                "```sh\nexit\n```\n\n",
+            )
+        );
+    }
+
+    #[test]
+    fn with_prompt_detection() {
+        let md = encode(&[
+            Event::output(
+                0,
+                b"Welcome\r\n(venv) me@box:~$ echo hi there\r\nhi there\r\n",
+            ),
+            Event::marker(1, "Done".to_owned()),
+            Event::output(2, b"(venv) me@box:~$ \r\n(venv) me@box:~$ "),
+        ]);
+
+        assert_eq!(
+            md,
+            concat!(
+                "# Demo\n\n",
+                "```\nWelcome\n```\n\n",
+                "```sh\necho hi there\n```\n\n",
+                "```\nhi there\n```\n\n",
+                "## Done\n\n",
+            )
+        );
+    }
+
+    #[test]
+    fn with_prompt_like_output() {
+        let md = encode(&[Event::output(
+            0,
+            b"$ cat README.md\r\n# Title\r\n#\r\n> quote\r\n>\r\n$ ",
+        )]);
+
+        assert_eq!(
+            md,
+            concat!(
+                "# Demo\n\n",
+                "```sh\ncat README.md\n```\n\n",
+                "```\n# Title\n#\n> quote\n>\n```\n\n",
+            )
+        );
+
+        let md = encode(&[Event::output(
+            0,
+            b"box% make\r\n50% done\r\n100%\r\nbox% make\r\n100%\r\n",
+        )]);
+
+        assert_eq!(
+            md,
+            concat!(
+                "# Demo\n\n",
+                "```sh\nmake\n```\n\n",
+                "```\n50% done\n100%\n```\n\n",
+                "```sh\nmake\n```\n\n",
+                "```\n100%\n```\n\n",
+            )
+        );
+    }
+
+    #[test]
+    fn with_right_prompt() {
+        let md = encode(&[Event::output(
+            0,
+            b"~> ls\x1b[16G~/src\r\nfoo\r\n~> \x1b[16G~/src",
+        )]);
+
+        assert_eq!(
+            md,
+            concat!("# Demo\n\n", "```sh\nls\n```\n\n", "```\nfoo\n```\n\n",)
+        );
+    }
+
+    #[test]
+    fn split_prompt() {
+        use super::split_prompt;
+
+        assert_eq!(split_prompt("$ ls -l"), Some(("$", "ls -l")));
+        assert_eq!(split_prompt("root@host:/# "), Some(("root@host:/#", "")));
+        assert_eq!(split_prompt("(venv) ~ ❯ x"), None);
+        assert_eq!(split_prompt("(venv) ❯ x"), Some(("(venv) ❯", "x")));
+        assert_eq!(split_prompt("total 42"), None);
+        assert_eq!(split_prompt(""), None);
+    }
+}
@@ -0,0 +1,216 @@
+use super::Finish;
+use crate::asciicast::{Event, EventData};
+use crate::tty;
+use std::io;
+
+const LAST_CUE_DURATION: u64 = 3_000_000;
+
+/// Output format of `MarkersEncoder`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MarkersFormat {
+    WebVtt,
+    Srt,
+    /// YouTube-style chapter list, one `M:SS label` line per marker.
+    Chapters,
+}
+
+/// Encodes markers as subtitle cues, each shown until the next marker (or
+/// the end of the recording), or as a chapter list. Event times are used
+/// as is, so idle time limit and speed should be applied before.
+pub struct MarkersEncoder<W: Finish> {
+    writer: W,
+    format: MarkersFormat,
+    markers: Vec<(u64, String)>,
+    end_time: u64,
+}
+
+impl<W: Finish> MarkersEncoder<W> {
+    pub fn new(writer: W, format: MarkersFormat) -> Self {
+        MarkersEncoder {
+            writer,
+            format,
+            markers: Vec::new(),
+            end_time: 0,
+        }
+    }
+
+    fn write_cues(&mut self) -> io::Result<()> {
+        let (separator, escape) = match self.format {
+            MarkersFormat::WebVtt => {
+                writeln!(self.writer, "WEBVTT\n")?;
+
+                ('.', true)
+            }
+
+            _ => (',', false),
+        };
+
+        for (i, (time, label)) in self.markers.iter().enumerate() {
+            let end = match self.markers.get(i + 1) {
+                Some((next, _)) => *next,
+                None if self.end_time > *time => self.end_time,
+                None => time + LAST_CUE_DURATION,
+            };
+
+            let text = if escape {
+                escape_vtt(label)
+            } else {
+                label.clone()
+            };
+
+            writeln!(
+                self.writer,
+                "{}\n{} --> {}\n{}\n",
+                i + 1,
+                cue_time(*time, separator),
+                cue_time(end, separator),
+                text
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn write_chapters(&mut self) -> io::Result<()> {
+        if self
+            .markers
+            .first()
+            .is_some_and(|(time, _)| *time >= 1_000_000)
+        {
+            writeln!(self.writer, "{} Start", chapter_time(0))?;
+        }
+
+        for (time, label) in &self.markers {
+            writeln!(
+                self.writer,
+                "{} {}",
+                chapter_time(*time),
+                label.lines().collect::<Vec<_>>().join(" ")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: Finish> super::Encoder for MarkersEncoder<W> {
+    fn start(&mut self, _timestamp: Option<u64>, _tty_size: &tty::TtySize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn event(&mut self, event: &Event) -> io::Result<()> {
+        self.end_time = self.end_time.max(event.time);
+
+        if let EventData::Marker(label) = &event.data {
+            let label = if label.trim().is_empty() {
+                format!("Marker {}", self.markers.len() + 1)
+            } else {
+                label
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            };
+
+            self.markers.push((event.time, label));
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        match self.format {
+            MarkersFormat::WebVtt | MarkersFormat::Srt => self.write_cues()?,
+            MarkersFormat::Chapters => self.write_chapters()?,
+        }
+
+        self.writer.finish()
+    }
+}
+
+/// Formats time as `HH:MM:SS.mmm` (or with a comma, for SRT).
+fn cue_time(time: u64, separator: char) -> String {
+    let millis = time / 1000;
+
+    format!(
+        "{:02}:{:02}:{:02}{separator}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// Formats time as `M:SS`, or `H:MM:SS` past the first hour.
+fn chapter_time(time: u64) -> String {
+    let secs = time / 1_000_000;
+
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+fn escape_vtt(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MarkersEncoder, MarkersFormat};
+    use crate::asciicast::Event;
+    use crate::encoder::Encoder;
+    use crate::tty::TtySize;
+
+    fn encode(format: MarkersFormat) -> String {
+        let mut out: Vec<u8> = Vec::new();
+        let mut enc = MarkersEncoder::new(&mut out, format);
+
+        enc.start(None, &TtySize(80, 24)).unwrap();
+        enc.event(&Event::output(0, b"$ ")).unwrap();
+        enc.event(&Event::marker(1_500_000, "Build & <test>".to_owned()))
+            .unwrap();
+        enc.event(&Event::output(2_000_000, b"make\r\n")).unwrap();
+        enc.event(&Event::marker(3_723_042_000, "".to_owned()))
+            .unwrap();
+        enc.event(&Event::output(3_725_000_000, b"ok\r\n")).unwrap();
+        enc.finish().unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn webvtt() {
+        assert_eq!(
+            encode(MarkersFormat::WebVtt),
+            concat!(
+                "WEBVTT\n\n",
+                "1\n00:00:01.500 --> 01:02:03.042\nBuild &amp; &lt;test&gt;\n\n",
+                "2\n01:02:03.042 --> 01:02:05.000\nMarker 2\n\n",
+            )
+        );
+    }
+
+    #[test]
+    fn srt() {
+        assert_eq!(
+            encode(MarkersFormat::Srt),
+            concat!(
+                "1\n00:00:01,500 --> 01:02:03,042\nBuild & <test>\n\n",
+                "2\n01:02:03,042 --> 01:02:05,000\nMarker 2\n\n",
+            )
+        );
+    }
+
+    #[test]
+    fn chapters() {
+        assert_eq!(
+            encode(MarkersFormat::Chapters),
+            "0:00 Start\n0:01 Build & <test>\n1:02:03 Marker 2\n"
+        );
+    }
+}
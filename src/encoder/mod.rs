@@ -1,17 +1,44 @@
+mod ansi;
 mod asciicast;
+mod font;
+mod frames;
+mod gif;
+mod html;
+mod keystrokes;
+mod markdown;
+mod markers;
+mod palette;
+mod png;
+mod raster;
 mod raw;
+mod screen;
+mod script;
+mod svg;
+mod ttyrec;
 mod txt;
 
-pub use asciicast::AsciicastEncoder;
-pub use asciicast::Metadata;
-pub use raw::RawEncoder;
-pub use txt::TextEncoder;
+pub(crate) use ansi::AnsiEncoder;
+pub(crate) use asciicast::AsciicastEncoder;
+pub(crate) use asciicast::Metadata;
+pub(crate) use frames::{FramesEncoder, SnapshotEvery};
+pub(crate) use gif::GifEncoder;
+pub(crate) use html::{HtmlEncoder, HtmlSnapshotEncoder};
+pub(crate) use keystrokes::KeystrokesEncoder;
+pub(crate) use markdown::MarkdownEncoder;
+pub(crate) use markers::{MarkersEncoder, MarkersFormat};
+pub(crate) use png::PngEncoder;
+pub(crate) use raw::RawEncoder;
+pub(crate) use script::ScriptEncoder;
+pub(crate) use svg::SvgEncoder;
+pub(crate) use ttyrec::TtyrecEncoder;
+pub(crate) use txt::TextEncoder;
 
 use crate::asciicast::Event;
 use crate::recorder;
 use crate::tty;
 use anyhow::Result;
-use std::io;
+use std::fs;
+use std::io::{self, Write};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 pub trait Encoder {
@@ -23,7 +50,30 @@ pub trait Encoder {
     }
 }
 
-pub trait EncoderExt {
+/// A writer encoders write to. `finish` is called once all output has been
+/// written, to flush it and complete formats with a trailer (e.g. compressed
+/// streams).
+pub(crate) trait Finish: Write {
+    fn finish(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+impl Finish for Vec<u8> {}
+
+impl Finish for fs::File {}
+
+impl Finish for io::Stdout {}
+
+impl<W: Write + ?Sized> Finish for &mut W {}
+
+impl<F: Finish + ?Sized> Finish for Box<F> {
+    fn finish(&mut self) -> io::Result<()> {
+        (**self).finish()
+    }
+}
+
+pub(crate) trait EncoderExt {
     fn encode(&mut self, recording: crate::asciicast::Asciicast) -> Result<()>;
 }
 
@@ -56,6 +106,10 @@ impl<E: Encoder> recorder::Output for E {
         self.event(&Event::output(time, data))
     }
 
+    fn bytes(&mut self, time: u64, data: &[u8]) -> io::Result<()> {
+        self.event(&Event::bytes(time, data))
+    }
+
     fn input(&mut self, time: u64, data: &[u8]) -> io::Result<()> {
         self.event(&Event::input(time, data))
     }
@@ -68,6 +122,10 @@ impl<E: Encoder> recorder::Output for E {
         self.event(&Event::marker(time, "".to_owned()))
     }
 
+    fn exit(&mut self, time: u64, status: i32) -> io::Result<()> {
+        self.event(&Event::exit(time, status))
+    }
+
     fn finish(&mut self) -> io::Result<()> {
         self.finish()
     }
@@ -0,0 +1,98 @@
+use crate::asciicast::Theme;
+use avt::{Color, Pen};
+use rgb::RGB8;
+
+// asciinema player's default theme, used when a recording doesn't have one.
+const DEFAULT_FG: RGB8 = RGB8::new(0xcc, 0xcc, 0xcc);
+const DEFAULT_BG: RGB8 = RGB8::new(0x12, 0x13, 0x14);
+
+const DEFAULT_PALETTE: [RGB8; 16] = [
+    RGB8::new(0x00, 0x00, 0x00),
+    RGB8::new(0xdd, 0x3c, 0x69),
+    RGB8::new(0x4e, 0xbf, 0x22),
+    RGB8::new(0xdd, 0xaf, 0x3c),
+    RGB8::new(0x26, 0xb0, 0xd7),
+    RGB8::new(0xb9, 0x54, 0xe1),
+    RGB8::new(0x54, 0xe1, 0xb9),
+    RGB8::new(0xd9, 0xd9, 0xd9),
+    RGB8::new(0x4d, 0x4d, 0x4d),
+    RGB8::new(0xdd, 0x3c, 0x69),
+    RGB8::new(0x4e, 0xbf, 0x22),
+    RGB8::new(0xdd, 0xaf, 0x3c),
+    RGB8::new(0x26, 0xb0, 0xd7),
+    RGB8::new(0xb9, 0x54, 0xe1),
+    RGB8::new(0x54, 0xe1, 0xb9),
+    RGB8::new(0xff, 0xff, 0xff),
+];
+
+/// Maps terminal colors to RGB, according to the recording's theme.
+#[derive(Clone, Debug)]
+pub struct Palette {
+    pub fg: RGB8,
+    pub bg: RGB8,
+    colors: [RGB8; 256],
+}
+
+impl Palette {
+    pub fn new(theme: Option<&Theme>) -> Self {
+        let (fg, bg, base) = match theme {
+            Some(theme) => (theme.fg, theme.bg, &theme.palette[..]),
+            None => (DEFAULT_FG, DEFAULT_BG, &DEFAULT_PALETTE[..]),
+        };
+
+        let mut colors = [RGB8::default(); 256];
+
+        // 8 color palettes use the same colors for bright variants
+        for (i, color) in colors.iter_mut().enumerate().take(16) {
+            *color = base[i % base.len()];
+        }
+
+        let levels = [0, 95, 135, 175, 215, 255];
+
+        for i in 0..216 {
+            colors[16 + i] = RGB8::new(levels[i / 36], levels[i / 6 % 6], levels[i % 6]);
+        }
+
+        for i in 0..24 {
+            let level = 8 + i as u8 * 10;
+            colors[232 + i] = RGB8::new(level, level, level);
+        }
+
+        Palette { fg, bg, colors }
+    }
+
+    pub fn color(&self, color: Color) -> RGB8 {
+        match color {
+            Color::Indexed(i) => self.colors[i as usize],
+            Color::RGB(c) => c,
+        }
+    }
+
+    /// Returns foreground and background colors of a cell, taking bold
+    /// (bright), faint and inverse attributes into account.
+    pub fn cell_colors(&self, pen: &Pen) -> (RGB8, RGB8) {
+        let mut fg = match pen.foreground() {
+            Some(Color::Indexed(i)) if i < 8 && pen.is_bold() => self.colors[i as usize + 8],
+            Some(color) => self.color(color),
+            None => self.fg,
+        };
+
+        let mut bg = pen.background().map_or(self.bg, |c| self.color(c));
+
+        if pen.is_faint() {
+            fg = blend(fg, bg);
+        }
+
+        if pen.is_inverse() {
+            std::mem::swap(&mut fg, &mut bg);
+        }
+
+        (fg, bg)
+    }
+}
+
+fn blend(a: RGB8, b: RGB8) -> RGB8 {
+    let mix = |a: u8, b: u8| ((a as u16 + b as u16) / 2) as u8;
+
+    RGB8::new(mix(a.r, b.r), mix(a.g, b.g), mix(a.b, b.b))
+}
@@ -0,0 +1,154 @@
+use super::palette::Palette;
+use super::raster::{self, Image};
+use super::screen::Screen;
+use super::Finish;
+use crate::asciicast::{Event, Theme};
+use crate::tty;
+use flate2::write::ZlibEncoder;
+use flate2::Crc;
+use std::io::{self, Write};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// Renders the terminal screen at the end of the (possibly cut) recording
+/// as a PNG image, using the built-in bitmap font.
+pub struct PngEncoder<W: Finish> {
+    writer: W,
+    palette: Palette,
+    screen: Option<Screen>,
+}
+
+impl<W: Finish> PngEncoder<W> {
+    pub fn new(writer: W, theme: Option<Theme>) -> Self {
+        PngEncoder {
+            writer,
+            palette: Palette::new(theme.as_ref()),
+            screen: None,
+        }
+    }
+
+    fn write_png(&mut self, image: &Image) -> io::Result<()> {
+        self.writer.write_all(&SIGNATURE)?;
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(image.width as u32).to_be_bytes());
+        header.extend_from_slice(&(image.height as u32).to_be_bytes());
+        // bit depth 8, truecolor, default compression, filter and interlace methods
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+        self.write_chunk(b"IHDR", &header)?;
+
+        let mut zlib = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+
+        for row in image.pixels.chunks(image.width.max(1)) {
+            let mut data = Vec::with_capacity(1 + row.len() * 3);
+            data.push(0);
+
+            for pixel in row {
+                data.extend_from_slice(&[pixel.r, pixel.g, pixel.b]);
+            }
+
+            zlib.write_all(&data)?;
+        }
+
+        self.write_chunk(b"IDAT", &zlib.finish()?)?;
+        self.write_chunk(b"IEND", &[])
+    }
+
+    fn write_chunk(&mut self, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+        let mut crc = Crc::new();
+        crc.update(kind);
+        crc.update(data);
+
+        self.writer.write_all(&(data.len() as u32).to_be_bytes())?;
+        self.writer.write_all(kind)?;
+        self.writer.write_all(data)?;
+        self.writer.write_all(&crc.sum().to_be_bytes())
+    }
+}
+
+impl<W: Finish> super::Encoder for PngEncoder<W> {
+    fn start(&mut self, _timestamp: Option<u64>, tty_size: &tty::TtySize) -> io::Result<()> {
+        self.screen = Some(Screen::new(tty_size));
+
+        Ok(())
+    }
+
+    fn event(&mut self, event: &Event) -> io::Result<()> {
+        self.screen.as_mut().unwrap().feed(event);
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let screen = self.screen.take().unwrap();
+        let image = raster::render(screen.vt(), &self.palette, screen.vt().size());
+        self.write_png(&image)?;
+
+        self.writer.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PngEncoder;
+    use crate::asciicast::Event;
+    use crate::encoder::Encoder;
+    use crate::tty::TtySize;
+    use flate2::read::ZlibDecoder;
+    use flate2::Crc;
+    use std::io::Read;
+
+    #[test]
+    fn encoder_impl() {
+        let mut out: Vec<u8> = Vec::new();
+        let mut enc = PngEncoder::new(&mut out, None);
+
+        enc.start(None, &TtySize(4, 2)).unwrap();
+        enc.event(&Event::output(0, b"\x1b[?25l\x1b[41m \x1b[0m#"))
+            .unwrap();
+        enc.event(&Event::resize(1, (3, 1))).unwrap();
+        enc.finish().unwrap();
+
+        assert_eq!(&out[..8], b"\x89PNG\r\n\x1a\n");
+
+        let mut chunks = Vec::new();
+        let mut rest = &out[8..];
+
+        while !rest.is_empty() {
+            let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let kind = String::from_utf8(rest[4..8].to_vec()).unwrap();
+            let data = rest[8..8 + len].to_vec();
+            let crc = u32::from_be_bytes(rest[8 + len..12 + len].try_into().unwrap());
+
+            let mut expected = Crc::new();
+            expected.update(&rest[4..8 + len]);
+            assert_eq!(crc, expected.sum());
+
+            chunks.push((kind, data));
+            rest = &rest[12 + len..];
+        }
+
+        let kinds: Vec<&str> = chunks.iter().map(|(kind, _)| kind.as_str()).collect();
+        assert_eq!(kinds, vec!["IHDR", "IDAT", "IEND"]);
+        assert_eq!(chunks[0].1, [0, 0, 0, 24, 0, 0, 0, 16, 8, 2, 0, 0, 0]);
+
+        let mut data = Vec::new();
+        ZlibDecoder::new(&chunks[1].1[..])
+            .read_to_end(&mut data)
+            .unwrap();
+
+        assert_eq!(data.len(), 16 * (1 + 24 * 3));
+
+        let pixel = |x: usize, y: usize| {
+            let i = y * (1 + 24 * 3) + 1 + x * 3;
+            (data[i], data[i + 1], data[i + 2])
+        };
+
+        // red background of the first cell, default background after it
+        assert_eq!(pixel(0, 0), (0xdd, 0x3c, 0x69));
+        assert_eq!(pixel(23, 0), (0x12, 0x13, 0x14));
+
+        // "#" glyph drawn in the default foreground color
+        assert!((0..8).any(|x| (0..16).any(|y| pixel(8 + x, y) == (0xcc, 0xcc, 0xcc))));
+    }
+}
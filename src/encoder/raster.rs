@@ -0,0 +1,96 @@
+use super::font;
+use super::palette::Palette;
+use rgb::RGB8;
+
+// Glyph rows are doubled, giving cells the usual 1:2 terminal aspect ratio.
+pub const CELL_WIDTH: usize = font::WIDTH;
+pub const CELL_HEIGHT: usize = font::HEIGHT * 2;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<RGB8>,
+}
+
+/// Renders the terminal screen onto an image of `cols` x `rows` cells.
+/// Lines and columns beyond that are cut off, missing ones are filled with
+/// the background color.
+pub fn render(vt: &avt::Vt, palette: &Palette, (cols, rows): (usize, usize)) -> Image {
+    let width = cols * CELL_WIDTH;
+    let height = rows * CELL_HEIGHT;
+    let mut pixels = vec![palette.bg; width * height];
+    let cursor = vt.cursor();
+
+    for (row, line) in vt.view().iter().take(rows).enumerate() {
+        for (col, (ch, pen)) in line.cells().take(cols).enumerate() {
+            let (mut fg, mut bg) = palette.cell_colors(&pen);
+
+            if cursor.visible && (cursor.col, cursor.row) == (col, row) {
+                std::mem::swap(&mut fg, &mut bg);
+            }
+
+            let glyph = font::glyph(ch);
+            let x0 = col * CELL_WIDTH;
+            let y0 = row * CELL_HEIGHT;
+
+            for y in 0..CELL_HEIGHT {
+                let mut bits = glyph[y / 2];
+
+                if pen.is_bold() {
+                    bits |= bits << 1;
+                }
+
+                if pen.is_italic() && y < CELL_HEIGHT / 2 {
+                    bits <<= 1;
+                }
+
+                if (pen.is_underline() && y == CELL_HEIGHT - 1)
+                    || (pen.is_strikethrough() && y == CELL_HEIGHT / 2)
+                {
+                    bits = 0xff;
+                }
+
+                let offset = (y0 + y) * width + x0;
+
+                for (x, pixel) in pixels[offset..offset + CELL_WIDTH].iter_mut().enumerate() {
+                    *pixel = if bits & (1 << x) != 0 { fg } else { bg };
+                }
+            }
+        }
+    }
+
+    Image {
+        width,
+        height,
+        pixels,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, CELL_HEIGHT, CELL_WIDTH};
+    use crate::encoder::palette::Palette;
+
+    #[test]
+    fn render_cells() {
+        let palette = Palette::new(None);
+        let mut vt = avt::Vt::new(4, 2);
+        vt.feed_str("\x1b[?25l\x1b[31;42m_\x1b[0m\r\n\x1b[7m \x1b[0m");
+
+        let image = render(&vt, &palette, (3, 3));
+        let pixel = |x: usize, y: usize| image.pixels[y * image.width + x];
+        let red = palette.color(avt::Color::Indexed(1));
+        let green = palette.color(avt::Color::Indexed(2));
+
+        assert_eq!(
+            (image.width, image.height),
+            (3 * CELL_WIDTH, 3 * CELL_HEIGHT)
+        );
+        assert_eq!(pixel(0, 0), green);
+        assert_eq!(pixel(0, CELL_HEIGHT - 1), red);
+        assert_eq!(pixel(0, CELL_HEIGHT), palette.fg);
+        assert_eq!(pixel(CELL_WIDTH, CELL_HEIGHT), palette.bg);
+        assert_eq!(pixel(0, 2 * CELL_HEIGHT), palette.bg);
+    }
+}
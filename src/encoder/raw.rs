@@ -1,47 +1,85 @@
-use crate::asciicast::{Event, EventData};
+use super::Finish;
+use crate::asciicast::{Event, EventData, Theme};
 use crate::tty;
-use std::io::{self, Write};
+use rgb::RGB8;
+use std::io;
 
 pub struct RawEncoder<W> {
     writer: W,
     append: bool,
+    theme: Option<Theme>,
 }
 
 impl<W> RawEncoder<W> {
-    pub fn new(writer: W, append: bool) -> Self {
-        RawEncoder { writer, append }
+    pub fn new(writer: W, append: bool, theme: Option<Theme>) -> Self {
+        RawEncoder {
+            writer,
+            append,
+            theme,
+        }
+    }
+}
+
+impl<W: Finish> RawEncoder<W> {
+    fn write_theme(&mut self) -> io::Result<()> {
+        if let Some(theme) = &self.theme {
+            write!(self.writer, "\x1b]10;{}\x07", osc_color(theme.fg))?;
+            write!(self.writer, "\x1b]11;{}\x07", osc_color(theme.bg))?;
+
+            for (i, color) in theme.palette.iter().enumerate() {
+                write!(self.writer, "\x1b]4;{};{}\x07", i, osc_color(*color))?;
+            }
+        }
+
+        Ok(())
     }
 }
 
-impl<W: Write> super::Encoder for RawEncoder<W> {
+fn osc_color(color: RGB8) -> String {
+    format!("rgb:{:02x}/{:02x}/{:02x}", color.r, color.g, color.b)
+}
+
+impl<W: Finish> super::Encoder for RawEncoder<W> {
     fn start(&mut self, _timestamp: Option<u64>, tty_size: &tty::TtySize) -> io::Result<()> {
         if self.append {
             Ok(())
         } else {
-            write!(self.writer, "\x1b[8;{};{}t", tty_size.1, tty_size.0)
+            write!(self.writer, "\x1b[8;{};{}t", tty_size.1, tty_size.0)?;
+
+            self.write_theme()
         }
     }
 
     fn event(&mut self, event: &Event) -> io::Result<()> {
-        if let EventData::Output(data) = &event.data {
-            self.writer.write_all(data.as_bytes())
-        } else {
-            Ok(())
+        match &event.data {
+            EventData::Output(data) => self.writer.write_all(data.as_bytes()),
+            EventData::Bytes(data) => self.writer.write_all(data),
+            _ => Ok(()),
         }
     }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if !self.append && self.theme.is_some() {
+            // reset the palette, foreground and background colors
+            write!(self.writer, "\x1b]104\x07\x1b]110\x07\x1b]111\x07")?;
+        }
+
+        self.writer.finish()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::RawEncoder;
-    use crate::asciicast::Event;
+    use crate::asciicast::{Event, Theme};
     use crate::encoder::Encoder;
     use crate::tty::TtySize;
+    use rgb::RGB8;
 
     #[test]
     fn encoder_impl() {
         let mut out: Vec<u8> = Vec::new();
-        let mut enc = RawEncoder::new(&mut out, false);
+        let mut enc = RawEncoder::new(&mut out, false, None);
 
         enc.start(None, &TtySize(100, 50)).unwrap();
         enc.event(&Event::output(0, b"he\x1b[1mllo\r\n")).unwrap();
@@ -53,4 +91,25 @@ mod tests {
 
         assert_eq!(out, b"\x1b[8;50;100the\x1b[1mllo\r\nworld\r\n");
     }
+
+    #[test]
+    fn theme() {
+        let mut out: Vec<u8> = Vec::new();
+
+        let theme = Theme {
+            fg: RGB8::new(0xff, 0xff, 0xff),
+            bg: RGB8::new(0, 0, 0),
+            palette: vec![RGB8::new(0x10, 0x20, 0x30); 8],
+        };
+
+        let mut enc = RawEncoder::new(&mut out, false, Some(theme));
+
+        enc.start(None, &TtySize(100, 50)).unwrap();
+        enc.finish().unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.starts_with("\x1b[8;50;100t\x1b]10;rgb:ff/ff/ff\x07\x1b]11;rgb:00/00/00\x07"));
+        assert!(out.ends_with("\x1b]4;7;rgb:10/20/30\x07\x1b]104\x07\x1b]110\x07\x1b]111\x07"));
+    }
 }
@@ -0,0 +1,128 @@
+use super::palette::Palette;
+use crate::asciicast::{Event, EventData};
+use crate::tty;
+use rgb::RGB8;
+
+/// Virtual terminal driven by the event stream, for encoders producing
+/// snapshots of the screen. Time is in microseconds.
+pub struct Screen {
+    vt: avt::Vt,
+    time: u64,
+    dirty: bool,
+}
+
+/// Cell attributes, with colors resolved through the palette.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Style {
+    pub fg: RGB8,
+    pub bg: RGB8,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikethrough: bool,
+}
+
+/// Consecutive cells of a line having the same style.
+#[derive(Clone, Debug)]
+pub struct Run {
+    pub col: usize,
+    pub style: Style,
+    pub text: String,
+}
+
+impl Screen {
+    pub fn new(tty_size: &tty::TtySize) -> Self {
+        let vt = avt::Vt::builder()
+            .size(tty_size.0 as usize, tty_size.1 as usize)
+            .resizable(true)
+            .scrollback_limit(0)
+            .build();
+
+        Screen {
+            vt,
+            time: 0,
+            dirty: true,
+        }
+    }
+
+    pub fn vt(&self) -> &avt::Vt {
+        &self.vt
+    }
+
+    /// Time since which the screen looks the way it does now.
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
+    /// Returns true when the screen changed since the last time this
+    /// returned true, and stays unchanged until an event at `time` (or
+    /// until the end, for `None`). It's the moment to take a snapshot.
+    pub fn changed_before(&mut self, time: Option<u64>) -> bool {
+        let changed = self.dirty && time.is_none_or(|time| time > self.time);
+
+        if changed {
+            self.dirty = false;
+        }
+
+        changed
+    }
+
+    pub fn feed(&mut self, event: &Event) {
+        self.time = self.time.max(event.time);
+
+        match &event.data {
+            EventData::Output(data) => self.feed_str(data),
+            EventData::Bytes(data) => self.feed_str(&String::from_utf8_lossy(data)),
+            EventData::Resize(cols, rows) => self.feed_str(&format!("\x1b[8;{rows};{cols}t")),
+            _ => (),
+        }
+    }
+
+    fn feed_str(&mut self, data: &str) {
+        self.vt.feed_str(data);
+        self.dirty = true;
+    }
+}
+
+/// Splits lines of the screen into runs of cells with the same style. The
+/// cursor, when visible, is shown by inverting colors of its cell.
+pub fn runs(vt: &avt::Vt, palette: &Palette) -> Vec<Vec<Run>> {
+    let cursor = vt.cursor();
+
+    vt.view()
+        .iter()
+        .enumerate()
+        .map(|(row, line)| {
+            let mut runs: Vec<Run> = Vec::new();
+
+            for (col, (ch, pen)) in line.cells().enumerate() {
+                let (mut fg, mut bg) = palette.cell_colors(&pen);
+
+                if cursor.visible && (cursor.col, cursor.row) == (col, row) {
+                    std::mem::swap(&mut fg, &mut bg);
+                }
+
+                let style = Style {
+                    fg,
+                    bg,
+                    bold: pen.is_bold(),
+                    italic: pen.is_italic(),
+                    underline: pen.is_underline(),
+                    strikethrough: pen.is_strikethrough(),
+                };
+
+                match runs.last_mut() {
+                    Some(run) if run.style == style => run.text.push(ch),
+
+                    _ => runs.push(Run {
+                        col,
+                        style,
+                        text: ch.to_string(),
+                    }),
+                }
+            }
+
+            runs
+        })
+        .collect()
+}
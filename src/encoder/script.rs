@@ -0,0 +1,183 @@
+use super::Finish;
+use crate::asciicast::{script, Event, EventData};
+use crate::tty;
+use std::io;
+
+/// Writes a script(1) log and its timing file, replayable with
+/// `scriptreplay --timing TIMING LOG`. The classic format only has the
+/// output stream, the advanced one also has input, resizes and exit status
+/// (replayable with `scriptreplay --log-timing TIMING --log-io LOG`).
+pub struct ScriptEncoder<W, T> {
+    log: W,
+    timing: T,
+    advanced: bool,
+    prev_time: u64,
+}
+
+impl<W, T> ScriptEncoder<W, T> {
+    pub fn new(log: W, timing: T, advanced: bool) -> Self {
+        ScriptEncoder {
+            log,
+            timing,
+            advanced,
+            prev_time: 0,
+        }
+    }
+}
+
+impl<W: Finish, T: Finish> ScriptEncoder<W, T> {
+    fn delay(&mut self, time: u64) -> String {
+        let delay = time.saturating_sub(self.prev_time);
+        self.prev_time = self.prev_time.max(time);
+
+        format!("{}.{:06}", delay / 1_000_000, delay % 1_000_000)
+    }
+
+    fn write_data(&mut self, code: &str, time: u64, data: &[u8]) -> io::Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let delay = self.delay(time);
+
+        if self.advanced {
+            writeln!(self.timing, "{code} {delay} {}", data.len())?;
+        } else {
+            writeln!(self.timing, "{delay} {}", data.len())?;
+        }
+
+        self.log.write_all(data)
+    }
+}
+
+impl<W: Finish, T: Finish> super::Encoder for ScriptEncoder<W, T> {
+    fn start(&mut self, timestamp: Option<u64>, tty_size: &tty::TtySize) -> io::Result<()> {
+        let start_time = script::format_time(timestamp.unwrap_or(0));
+
+        writeln!(
+            self.log,
+            "Script started on {} [COLUMNS=\"{}\" LINES=\"{}\"]",
+            start_time, tty_size.0, tty_size.1
+        )?;
+
+        if self.advanced {
+            if timestamp.is_some() {
+                writeln!(self.timing, "H 0.000000 START_TIME {start_time}")?;
+            }
+
+            writeln!(self.timing, "H 0.000000 COLUMNS {}", tty_size.0)?;
+            writeln!(self.timing, "H 0.000000 LINES {}", tty_size.1)?;
+        }
+
+        Ok(())
+    }
+
+    fn event(&mut self, event: &Event) -> io::Result<()> {
+        match &event.data {
+            EventData::Output(data) => self.write_data("O", event.time, data.as_bytes()),
+            EventData::Bytes(data) => self.write_data("O", event.time, data),
+
+            EventData::Input(data) if self.advanced => {
+                self.write_data("I", event.time, data.as_bytes())
+            }
+
+            EventData::Resize(cols, rows) if self.advanced => {
+                let delay = self.delay(event.time);
+
+                writeln!(self.timing, "S {delay} SIGWINCH ROWS={rows} COLS={cols}")
+            }
+
+            EventData::Exit(status) if self.advanced => {
+                let delay = self.delay(event.time);
+
+                writeln!(self.timing, "H {delay} EXIT_CODE {status}")
+            }
+
+            _ => Ok(()),
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.log.finish()?;
+        self.timing.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScriptEncoder;
+    use crate::asciicast::script::{self, InputLog};
+    use crate::asciicast::{Asciicast, Event};
+    use crate::encoder::Encoder;
+    use crate::tty::TtySize;
+    use anyhow::Result;
+    use std::io;
+
+    fn events() -> Vec<Event> {
+        vec![
+            Event::output(500_000, b"$ "),
+            Event::input(1_000_000, b"ls\r"),
+            Event::output(1_250_000, "żółć\r\n".as_bytes()),
+            Event::resize(2_000_000, (120, 40)),
+            Event::output(3_000_001, b"$ "),
+            Event::exit(3_500_000, 0),
+        ]
+    }
+
+    fn encode(advanced: bool) -> (Vec<u8>, String) {
+        let mut log = Vec::new();
+        let mut timing = Vec::new();
+        let mut enc = ScriptEncoder::new(&mut log, &mut timing, advanced);
+
+        enc.start(Some(1704110400), &TtySize(100, 30)).unwrap();
+
+        for event in events() {
+            enc.event(&event).unwrap();
+        }
+
+        enc.finish().unwrap();
+
+        (log, String::from_utf8(timing).unwrap())
+    }
+
+    #[test]
+    fn classic() {
+        let (log, timing) = encode(false);
+
+        assert_eq!(
+            String::from_utf8_lossy(&log),
+            "Script started on 2024-01-01 12:00:00+00:00 [COLUMNS=\"100\" LINES=\"30\"]\n$ żółć\r\n$ "
+        );
+
+        assert_eq!(timing, "0.500000 2\n0.750000 10\n1.750001 2\n");
+
+        let Asciicast { header, events, .. } = script::open(
+            timing.as_bytes(),
+            Box::new(io::Cursor::new(log)),
+            InputLog::None,
+        )
+        .unwrap();
+
+        assert_eq!((header.cols, header.rows), (100, 30));
+        assert_eq!(header.timestamp, Some(1704110400));
+        assert_eq!(events.collect::<Result<Vec<_>>>().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn advanced() {
+        let (log, timing) = encode(true);
+
+        assert!(timing.starts_with("H 0.000000 START_TIME 2024-01-01 12:00:00+00:00\n"));
+        assert!(timing.contains("I 0.500000 3\n"));
+        assert!(timing.contains("S 0.750000 SIGWINCH ROWS=40 COLS=120\n"));
+
+        let Asciicast { events, .. } = script::open(
+            timing.as_bytes(),
+            Box::new(io::Cursor::new(log)),
+            InputLog::Combined,
+        )
+        .unwrap();
+
+        assert_eq!(events.collect::<Result<Vec<_>>>().unwrap(), self::events());
+    }
+}
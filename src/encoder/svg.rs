@@ -0,0 +1,290 @@
+use super::palette::Palette;
+use super::screen::{self, Run, Screen};
+use super::Finish;
+use crate::asciicast::{Event, Theme};
+use crate::tty;
+use rgb::RGB8;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io;
+
+const FONT_FAMILY: &str = "'SFMono-Regular', Menlo, Consolas, 'Liberation Mono', monospace";
+const FONT_SIZE: f64 = 14.0;
+const CELL_WIDTH: f64 = FONT_SIZE * 0.6;
+const LINE_HEIGHT: f64 = FONT_SIZE * 1.25;
+const BASELINE: f64 = FONT_SIZE * 0.95;
+const PADDING: f64 = 8.0;
+const LAST_FRAME_DURATION: u64 = 3_000_000;
+
+/// Encodes the recording as animated SVG, with one group per distinct
+/// screen state, shown and hidden by CSS animation.
+pub struct SvgEncoder<W: Finish> {
+    writer: W,
+    palette: Palette,
+    screen: Option<Screen>,
+    size: (usize, usize),
+    states: Vec<String>,
+    state_ids: HashMap<String, usize>,
+    timeline: Vec<(u64, usize)>,
+}
+
+impl<W: Finish> SvgEncoder<W> {
+    pub fn new(writer: W, theme: Option<Theme>) -> Self {
+        SvgEncoder {
+            writer,
+            palette: Palette::new(theme.as_ref()),
+            screen: None,
+            size: (0, 0),
+            states: Vec::new(),
+            state_ids: HashMap::new(),
+            timeline: Vec::new(),
+        }
+    }
+
+    fn capture(&mut self) {
+        let screen = self.screen.as_ref().unwrap();
+        let (cols, rows) = screen.vt().size();
+        self.size = (self.size.0.max(cols), self.size.1.max(rows));
+        let state = render(screen.vt(), &self.palette);
+
+        let id = *self.state_ids.entry(state).or_insert_with_key(|state| {
+            self.states.push(state.clone());
+            self.states.len() - 1
+        });
+
+        match self.timeline.last() {
+            Some((_, last_id)) if *last_id == id => (),
+            _ => self.timeline.push((screen.time(), id)),
+        }
+    }
+
+    fn write_svg(&mut self) -> io::Result<()> {
+        let width = self.size.0 as f64 * CELL_WIDTH + 2.0 * PADDING;
+        let height = self.size.1 as f64 * LINE_HEIGHT + 2.0 * PADDING;
+        let start = self.timeline.first().map_or(0, |(time, _)| *time);
+        let end = self.timeline.last().map_or(0, |(time, _)| *time) + LAST_FRAME_DURATION;
+        let duration = end - start;
+
+        writeln!(self.writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+
+        writeln!(
+            self.writer,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width:.1}" height="{height:.1}" viewBox="0 0 {width:.1} {height:.1}" font-family="{FONT_FAMILY}" font-size="{FONT_SIZE}">"#
+        )?;
+
+        writeln!(self.writer, "<style>")?;
+        writeln!(self.writer, "text {{ white-space: pre; }}")?;
+
+        if self.states.len() > 1 {
+            writeln!(
+                self.writer,
+                ".s {{ visibility: hidden; animation: {:.3}s step-end infinite; }}",
+                duration as f64 / 1_000_000.0
+            )?;
+
+            for id in 0..self.states.len() {
+                write!(self.writer, "@keyframes k{id} {{")?;
+
+                for (i, (time, state)) in self.timeline.iter().enumerate() {
+                    if *state != id {
+                        continue;
+                    }
+
+                    let next = self.timeline.get(i + 1).map_or(end, |(time, _)| *time);
+                    let percent = |t: u64| (t - start) as f64 * 100.0 / duration as f64;
+
+                    write!(
+                        self.writer,
+                        " {:.3}% {{ visibility: visible }} {:.3}% {{ visibility: hidden }}",
+                        percent(*time),
+                        percent(next)
+                    )?;
+                }
+
+                writeln!(self.writer, " }}")?;
+                writeln!(self.writer, "#s{id} {{ animation-name: k{id}; }}")?;
+            }
+        }
+
+        writeln!(self.writer, "</style>")?;
+
+        writeln!(
+            self.writer,
+            r#"<rect width="100%" height="100%" fill="{}"/>"#,
+            color(self.palette.bg)
+        )?;
+
+        for (id, state) in self.states.iter().enumerate() {
+            writeln!(
+                self.writer,
+                r#"<g id="s{id}" class="s" transform="translate({PADDING} {PADDING})">"#
+            )?;
+
+            self.writer.write_all(state.as_bytes())?;
+            writeln!(self.writer, "</g>")?;
+        }
+
+        writeln!(self.writer, "</svg>")
+    }
+}
+
+impl<W: Finish> super::Encoder for SvgEncoder<W> {
+    fn start(&mut self, _timestamp: Option<u64>, tty_size: &tty::TtySize) -> io::Result<()> {
+        self.size = (tty_size.0 as usize, tty_size.1 as usize);
+        self.screen = Some(Screen::new(tty_size));
+
+        Ok(())
+    }
+
+    fn event(&mut self, event: &Event) -> io::Result<()> {
+        if self
+            .screen
+            .as_mut()
+            .unwrap()
+            .changed_before(Some(event.time))
+        {
+            self.capture();
+        }
+
+        self.screen.as_mut().unwrap().feed(event);
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if self.screen.as_mut().unwrap().changed_before(None) {
+            self.capture();
+        }
+
+        self.write_svg()?;
+        self.writer.finish()
+    }
+}
+
+/// Renders the screen as background rects and a `<text>` element per line.
+fn render(vt: &avt::Vt, palette: &Palette) -> String {
+    let mut svg = String::new();
+
+    for (row, mut runs) in screen::runs(vt, palette).into_iter().enumerate() {
+        let y = row as f64 * LINE_HEIGHT;
+
+        for run in &runs {
+            if run.style.bg != palette.bg {
+                let _ = writeln!(
+                    svg,
+                    r#"<rect x="{:.1}" y="{y:.1}" width="{:.1}" height="{LINE_HEIGHT:.1}" fill="{}"/>"#,
+                    run.col as f64 * CELL_WIDTH,
+                    run.text.chars().count() as f64 * CELL_WIDTH,
+                    color(run.style.bg)
+                );
+            }
+        }
+
+        let text_len = runs
+            .iter()
+            .rposition(|run| {
+                run.text.chars().any(|c| c != ' ') || run.style.underline || run.style.strikethrough
+            })
+            .map_or(0, |i| i + 1);
+
+        if text_len == 0 {
+            continue;
+        }
+
+        let _ = write!(svg, r#"<text y="{:.1}">"#, y + BASELINE);
+
+        runs.truncate(text_len);
+        let last = runs.last_mut().unwrap();
+
+        if !last.style.underline && !last.style.strikethrough {
+            last.text.truncate(last.text.trim_end_matches(' ').len());
+        }
+
+        for Run { col, style, text } in &runs {
+            let _ = write!(
+                svg,
+                r#"<tspan x="{:.1}" fill="{}""#,
+                *col as f64 * CELL_WIDTH,
+                color(style.fg)
+            );
+
+            if style.bold {
+                svg.push_str(r#" font-weight="bold""#);
+            }
+
+            if style.italic {
+                svg.push_str(r#" font-style="italic""#);
+            }
+
+            match (style.underline, style.strikethrough) {
+                (true, true) => svg.push_str(r#" text-decoration="underline line-through""#),
+                (true, false) => svg.push_str(r#" text-decoration="underline""#),
+                (false, true) => svg.push_str(r#" text-decoration="line-through""#),
+                (false, false) => (),
+            }
+
+            let _ = write!(svg, ">{}</tspan>", escape(text));
+        }
+
+        svg.push_str("</text>\n");
+    }
+
+    svg
+}
+
+fn color(color: RGB8) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            c if c.is_control() => escaped.push(' '),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SvgEncoder;
+    use crate::asciicast::Event;
+    use crate::encoder::Encoder;
+    use crate::tty::TtySize;
+
+    #[test]
+    fn encoder_impl() {
+        let mut out: Vec<u8> = Vec::new();
+        let mut enc = SvgEncoder::new(&mut out, None);
+
+        enc.start(None, &TtySize(10, 2)).unwrap();
+        enc.event(&Event::output(0, b"\x1b[?25l$ ")).unwrap();
+        enc.event(&Event::output(1_000_000, b"\x1b[1;31m<a&b>\x1b[0m"))
+            .unwrap();
+        enc.event(&Event::output(2_000_000, b"\x1b[3D\x1b[3C"))
+            .unwrap();
+        enc.event(&Event::output(3_000_000, b"\r\x1b[K$ ")).unwrap();
+        enc.finish().unwrap();
+
+        let svg = String::from_utf8(out).unwrap();
+
+        assert!(svg.starts_with("<?xml"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert!(svg.contains(r##"<tspan x="0.0" fill="#cccccc">$ </tspan><tspan x="16.8" fill="#dd3c69" font-weight="bold">&lt;a&amp;b&gt;</tspan>"##));
+
+        // the prompt state shows twice, with the cursor movement not changing the screen
+        assert_eq!(svg.matches("<g id=").count(), 2);
+        assert!(svg.contains(".s { visibility: hidden; animation: 6.000s step-end infinite; }"));
+        assert!(svg.contains("@keyframes k0 { 0.000% { visibility: visible } 16.667% { visibility: hidden } 50.000% { visibility: visible } 100.000% { visibility: hidden } }"));
+        assert!(svg.contains(
+            "@keyframes k1 { 16.667% { visibility: visible } 50.000% { visibility: hidden } }"
+        ));
+    }
+}
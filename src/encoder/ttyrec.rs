@@ -0,0 +1,117 @@
+use super::Finish;
+use crate::asciicast::{Event, EventData};
+use crate::tty;
+use std::io;
+
+pub struct TtyrecEncoder<W> {
+    writer: W,
+    append: bool,
+    start_time: u64,
+}
+
+impl<W> TtyrecEncoder<W> {
+    pub fn new(writer: W, append: bool) -> Self {
+        TtyrecEncoder {
+            writer,
+            append,
+            start_time: 0,
+        }
+    }
+}
+
+impl<W: Finish> TtyrecEncoder<W> {
+    fn write_frame(&mut self, time: u64, data: &[u8]) -> io::Result<()> {
+        let time = self.start_time + time;
+        let sec = (time / 1_000_000) as u32;
+        let usec = (time % 1_000_000) as u32;
+
+        self.writer.write_all(&sec.to_le_bytes())?;
+        self.writer.write_all(&usec.to_le_bytes())?;
+        self.writer.write_all(&(data.len() as u32).to_le_bytes())?;
+        self.writer.write_all(data)
+    }
+}
+
+fn resize_seq(cols: u16, rows: u16) -> String {
+    format!("\x1b[8;{rows};{cols}t")
+}
+
+impl<W: Finish> super::Encoder for TtyrecEncoder<W> {
+    fn start(&mut self, timestamp: Option<u64>, tty_size: &tty::TtySize) -> io::Result<()> {
+        self.start_time = timestamp.unwrap_or(0) * 1_000_000;
+
+        if self.append {
+            Ok(())
+        } else {
+            self.write_frame(0, resize_seq(tty_size.0, tty_size.1).as_bytes())
+        }
+    }
+
+    fn event(&mut self, event: &Event) -> io::Result<()> {
+        match &event.data {
+            EventData::Output(data) => self.write_frame(event.time, data.as_bytes()),
+            EventData::Bytes(data) => self.write_frame(event.time, data),
+
+            EventData::Resize(cols, rows) => {
+                self.write_frame(event.time, resize_seq(*cols, *rows).as_bytes())
+            }
+
+            _ => Ok(()),
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TtyrecEncoder;
+    use crate::asciicast::{self, Asciicast, Event, EventData};
+    use crate::encoder::Encoder;
+    use crate::tty::TtySize;
+    use anyhow::Result;
+
+    #[test]
+    fn encoder_impl() {
+        let mut out: Vec<u8> = Vec::new();
+        let mut enc = TtyrecEncoder::new(&mut out, false);
+
+        enc.start(Some(1700000000), &TtySize(100, 50)).unwrap();
+        enc.event(&Event::output(1_500_000, b"hello")).unwrap();
+        enc.event(&Event::input(2_000_000, b".")).unwrap();
+        enc.event(&Event::resize(3_000_000, (80, 24))).unwrap();
+        enc.event(&Event::bytes(4_000_000, b"\xff")).unwrap();
+        enc.event(&Event::marker(5_000_000, ".".to_owned()))
+            .unwrap();
+        enc.finish().unwrap();
+
+        assert_eq!(&out[..4], &1700000000u32.to_le_bytes());
+        assert_eq!(&out[12..23], b"\x1b[8;50;100t");
+        assert_eq!(&out[23..27], &1700000001u32.to_le_bytes());
+        assert_eq!(&out[27..31], &500_000u32.to_le_bytes());
+        assert_eq!(&out[31..35], &5u32.to_le_bytes());
+        assert_eq!(&out[35..40], b"hello");
+
+        let Asciicast { header, events, .. } = asciicast::open(&out[..]).unwrap();
+        let events = events.collect::<Result<Vec<Event>>>().unwrap();
+
+        assert_eq!((header.cols, header.rows), (100, 50));
+        assert_eq!(header.timestamp, Some(1700000000));
+        assert_eq!(events[1], Event::output(1_500_000, b"hello"));
+        assert!(events.contains(&Event::resize(3_000_000, (80, 24))));
+        assert_eq!(events.last().unwrap().data, EventData::Bytes(vec![0xff]));
+    }
+
+    #[test]
+    fn append() {
+        let mut out: Vec<u8> = Vec::new();
+        let mut enc = TtyrecEncoder::new(&mut out, true);
+
+        enc.start(Some(1), &TtySize(100, 50)).unwrap();
+        enc.event(&Event::output(0, b"x")).unwrap();
+
+        assert_eq!(out.len(), 13);
+    }
+}
@@ -1,23 +1,40 @@
+use super::Finish;
 use crate::asciicast::{Event, EventData};
 use crate::tty;
 use avt::util::{TextCollector, TextCollectorOutput};
-use std::io::{self, Write};
+use std::io;
+use std::sync::mpsc;
 
-pub struct TextEncoder<W: Write> {
-    writer: Option<W>,
-    collector: Option<TextCollector<TextWriter<W>>>,
+pub struct TextEncoder<W: Finish> {
+    writer: W,
+    collector: Option<TextCollector<TextSender>>,
+    lines: mpsc::Receiver<String>,
+    sender: Option<mpsc::Sender<String>>,
 }
 
-impl<W: Write> TextEncoder<W> {
+impl<W: Finish> TextEncoder<W> {
     pub fn new(writer: W) -> Self {
+        let (sender, lines) = mpsc::channel();
+
         TextEncoder {
-            writer: Some(writer),
+            writer,
             collector: None,
+            lines,
+            sender: Some(sender),
         }
     }
+
+    fn write_lines(&mut self) -> io::Result<()> {
+        for line in self.lines.try_iter() {
+            self.writer.write_all(line.as_bytes())?;
+            self.writer.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
 }
 
-impl<W: Write> super::Encoder for TextEncoder<W> {
+impl<W: Finish> super::Encoder for TextEncoder<W> {
     fn start(&mut self, _timestamp: Option<u64>, tty_size: &tty::TtySize) -> io::Result<()> {
         let vt = avt::Vt::builder()
             .size(tty_size.0 as usize, tty_size.1 as usize)
@@ -27,7 +44,7 @@ impl<W: Write> super::Encoder for TextEncoder<W> {
 
         self.collector = Some(TextCollector::new(
             vt,
-            TextWriter(self.writer.take().unwrap()),
+            TextSender(self.sender.take().unwrap()),
         ));
 
         Ok(())
@@ -36,26 +53,36 @@ impl<W: Write> super::Encoder for TextEncoder<W> {
     fn event(&mut self, event: &Event) -> io::Result<()> {
         use EventData::*;
 
+        let collector = self.collector.as_mut().unwrap();
+
         match &event.data {
-            Output(data) => self.collector.as_mut().unwrap().feed_str(data),
-            Resize(cols, rows) => self.collector.as_mut().unwrap().resize(*cols, *rows),
-            _ => Ok(()),
+            Output(data) => collector.feed_str(data)?,
+            Bytes(data) => collector.feed_str(&String::from_utf8_lossy(data))?,
+            Resize(cols, rows) => collector.resize(*cols, *rows)?,
+            _ => (),
         }
+
+        self.write_lines()
     }
 
     fn finish(&mut self) -> io::Result<()> {
-        self.collector.as_mut().unwrap().flush()
+        self.collector.as_mut().unwrap().flush()?;
+        self.write_lines()?;
+
+        self.writer.finish()
     }
 }
 
-struct TextWriter<W: Write>(W);
+/// Passes lines from the collector (which owns its output) to the encoder.
+struct TextSender(mpsc::Sender<String>);
 
-impl<W: Write> TextCollectorOutput for TextWriter<W> {
+impl TextCollectorOutput for TextSender {
     type Error = io::Error;
 
     fn push(&mut self, line: String) -> Result<(), Self::Error> {
-        self.0.write_all(line.as_bytes())?;
-        self.0.write_all(b"\n")
+        self.0
+            .send(line)
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
     }
 }
 
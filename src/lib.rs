@@ -0,0 +1,56 @@
+//! Reading, writing and converting of [asciinema](https://asciinema.org)
+//! terminal session recordings.
+//!
+//! ```no_run
+//! use asciinema::asciicast::{self, EventData};
+//!
+//! let recording = asciicast::open_from_path("demo.cast")?;
+//!
+//! for event in recording.events {
+//!     if let EventData::Output(text) = event?.data {
+//!         print!("{text}");
+//!     }
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! # Stability
+//!
+//! The recording data types of the [`asciicast`] module ([`Header`],
+//! [`Event`], [`EventData`], [`Theme`], [`Version`] and [`TimePolicy`]), its
+//! `open*` functions and writers, and the [`encoder::Encoder`] trait follow
+//! semantic versioning: breaking changes to them only happen in major
+//! releases. The data types are `#[non_exhaustive]`, so fields and variants
+//! may be added in minor releases; create them with their constructors.
+//! Everything else, including the serialized form of the data types, may
+//! change in any release.
+//!
+//! [`Header`]: asciicast::Header
+//! [`Event`]: asciicast::Event
+//! [`EventData`]: asciicast::EventData
+//! [`Theme`]: asciicast::Theme
+//! [`Version`]: asciicast::Version
+//! [`TimePolicy`]: asciicast::TimePolicy
+
+pub mod asciicast;
+pub mod encoder;
+pub mod recorder;
+pub mod tty;
+
+mod cmd;
+mod compress;
+mod config;
+mod io;
+mod locale;
+mod logger;
+mod notifier;
+mod player;
+mod pty;
+mod utf8;
+mod util;
+
+/// Entry point of the `asciinema` binary, not a part of the library API.
+#[doc(hidden)]
+pub fn main() -> anyhow::Result<()> {
+    cmd::main()
+}
@@ -3,4 +3,10 @@ macro_rules! info {
     ($fmt:expr, $($arg:tt)*) => (println!(concat!("::: ", $fmt), $($arg)*));
 }
 
+macro_rules! warning {
+    ($fmt:expr) => (eprintln!(concat!("::: ", $fmt)));
+    ($fmt:expr, $($arg:tt)*) => (eprintln!(concat!("::: ", $fmt), $($arg)*));
+}
+
 pub(crate) use info;
+pub(crate) use warning as warn;
@@ -40,7 +40,7 @@ pub fn play(
     let mut pause_elapsed_time: Option<u64> = None;
     let mut next_event = events.next().transpose()?;
 
-    while let Some(Event { time, data }) = &next_event {
+    while let Some(Event { time, data, .. }) = &next_event {
         if let Some(pet) = pause_elapsed_time {
             if let Some(input) = read_input(&mut tty, 1_000_000)? {
                 if keys.quit.as_ref().is_some_and(|k| k == &input) {
@@ -54,14 +54,17 @@ pub fn play(
                 } else if keys.step.as_ref().is_some_and(|k| k == &input) {
                     pause_elapsed_time = Some(*time);
 
-                    if let EventData::Output(data) = data {
-                        stdout.write_all(data.as_bytes())?;
-                        stdout.flush()?;
+                    match data {
+                        EventData::Output(data) => stdout.write_all(data.as_bytes())?,
+                        EventData::Bytes(data) => stdout.write_all(data)?,
+                        _ => (),
                     }
 
+                    stdout.flush()?;
+
                     next_event = events.next().transpose()?;
                 } else if keys.next_marker.as_ref().is_some_and(|k| k == &input) {
-                    while let Some(Event { time, data }) = next_event {
+                    while let Some(Event { time, data, .. }) = next_event {
                         next_event = events.next().transpose()?;
 
                         match data {
@@ -69,6 +72,10 @@ pub fn play(
                                 stdout.write_all(data.as_bytes())?;
                             }
 
+                            EventData::Bytes(data) => {
+                                stdout.write_all(&data)?;
+                            }
+
                             EventData::Marker(_) => {
                                 pause_elapsed_time = Some(time);
                                 break;
@@ -82,7 +89,7 @@ pub fn play(
                 }
             }
         } else {
-            while let Some(Event { time, data }) = &next_event {
+            while let Some(Event { time, data, .. }) = &next_event {
                 let delay = *time as i64 - epoch.elapsed().as_micros() as i64;
 
                 if delay > 0 {
@@ -108,12 +115,14 @@ pub fn play(
                         stdout.write_all(data.as_bytes())?;
                     }
 
-                    EventData::Marker(_) => {
-                        if pause_on_markers {
-                            pause_elapsed_time = Some(*time);
-                            next_event = events.next().transpose()?;
-                            break;
-                        }
+                    EventData::Bytes(data) => {
+                        stdout.write_all(data)?;
+                    }
+
+                    EventData::Marker(_) if pause_on_markers => {
+                        pause_elapsed_time = Some(*time);
+                        next_event = events.next().transpose()?;
+                        break;
                     }
 
                     _ => (),
@@ -11,7 +11,7 @@ use std::ffi::{CString, NulError};
 use std::io::{self, ErrorKind, Read, Write};
 use std::os::fd::{AsFd, RawFd};
 use std::os::fd::{BorrowedFd, OwnedFd};
-use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd};
 use std::{env, fs};
 
 type ExtraEnv = HashMap<String, String>;
@@ -36,7 +36,7 @@ pub fn exec<S: AsRef<str>, T: Tty + ?Sized, R: Recorder>(
 
     match result.fork_result {
         ForkResult::Parent { child } => handle_parent(
-            result.master.as_raw_fd(),
+            result.master.into_raw_fd(),
             child,
             tty,
             winsize_override,
@@ -290,7 +290,7 @@ fn read_non_blocking<R: Read + ?Sized>(
             } else if e.raw_os_error().is_some_and(|code| code == 5) {
                 Ok(Some(0))
             } else {
-                return Err(e);
+                Err(e)
             }
         }
     }
@@ -306,7 +306,7 @@ fn write_non_blocking<W: Write + ?Sized>(sink: &mut W, buf: &[u8]) -> io::Result
             } else if e.raw_os_error().is_some_and(|code| code == 5) {
                 Ok(Some(0))
             } else {
-                return Err(e);
+                Err(e)
             }
         }
     }
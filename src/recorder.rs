@@ -2,16 +2,18 @@ use crate::config::Key;
 use crate::notifier::Notifier;
 use crate::pty;
 use crate::tty;
+use crate::utf8::{self, Chunk, Utf8Decoder};
 use std::io;
 use std::sync::mpsc;
 use std::thread;
 use std::time::{Duration, Instant};
 
-pub struct Recorder {
+pub(crate) struct Recorder {
     output: Option<Box<dyn Output + Send>>,
     start_time: Instant,
     pause_time: Option<u64>,
     record_input: bool,
+    lossless: bool,
     keys: KeyBindings,
     notifier: Option<Box<dyn Notifier>>,
     sender: mpsc::Sender<Message>,
@@ -23,10 +25,18 @@ pub struct Recorder {
 pub trait Output {
     fn start(&mut self, tty_size: &tty::TtySize) -> io::Result<()>;
     fn output(&mut self, time: u64, data: &[u8]) -> io::Result<()>;
+
+    fn bytes(&mut self, time: u64, data: &[u8]) -> io::Result<()> {
+        self.output(time, data)
+    }
     fn input(&mut self, time: u64, data: &[u8]) -> io::Result<()>;
     fn resize(&mut self, time: u64, size: (u16, u16)) -> io::Result<()>;
     fn marker(&mut self, time: u64) -> io::Result<()>;
 
+    fn exit(&mut self, _time: u64, _status: i32) -> io::Result<()> {
+        Ok(())
+    }
+
     fn finish(&mut self) -> io::Result<()> {
         Ok(())
     }
@@ -37,6 +47,7 @@ enum Message {
     Input(u64, Vec<u8>),
     Resize(u64, tty::TtySize),
     Marker(u64),
+    Exit(u64, i32),
     Notification(String),
 }
 
@@ -46,6 +57,7 @@ impl Recorder {
     pub fn new(
         output: Box<dyn Output + Send>,
         record_input: bool,
+        lossless: bool,
         keys: KeyBindings,
         notifier: Box<dyn Notifier>,
     ) -> Self {
@@ -56,6 +68,7 @@ impl Recorder {
             start_time: Instant::now(),
             pause_time: None,
             record_input,
+            lossless,
             keys,
             notifier: Some(notifier),
             sender,
@@ -73,6 +86,11 @@ impl Recorder {
         }
     }
 
+    pub fn exit(&mut self, status: i32) {
+        let msg = Message::Exit(self.elapsed_time(), status);
+        self.sender.send(msg).expect("exit send should succeed");
+    }
+
     fn notify<S: ToString>(&self, text: S) {
         let msg = Message::Notification(text.to_string());
 
@@ -82,25 +100,62 @@ impl Recorder {
     }
 }
 
+/// Writes what's left in the output decoder (an incomplete UTF-8 sequence).
+fn flush_decoder(
+    output: &mut Box<dyn Output + Send>,
+    decoder: &mut Utf8Decoder,
+    time: u64,
+    lossless: bool,
+) {
+    if let Some(bytes) = decoder.finish() {
+        let _ = if lossless {
+            output.bytes(time, &bytes)
+        } else {
+            output.output(time, &bytes)
+        };
+    }
+}
+
 impl pty::Recorder for Recorder {
     fn start(&mut self, tty_size: tty::TtySize) -> io::Result<()> {
         let mut output = self.output.take().unwrap();
         output.start(&tty_size)?;
         let receiver = self.receiver.take().unwrap();
         let mut notifier = self.notifier.take().unwrap();
+        let lossless = self.lossless;
 
         let handle = thread::spawn(move || {
             use Message::*;
             let mut last_tty_size = tty_size;
+            let mut last_time = 0;
+            let mut output_decoder = Utf8Decoder::default();
+            let mut input_decoder = Utf8Decoder::default();
 
             for msg in receiver {
                 match msg {
                     Output(time, data) => {
-                        let _ = output.output(time, &data);
+                        let chunks = output_decoder.feed(&data);
+
+                        if lossless {
+                            for chunk in chunks {
+                                let _ = match chunk {
+                                    Chunk::Text(text) => output.output(time, text.as_bytes()),
+                                    Chunk::Invalid(bytes) => output.bytes(time, &bytes),
+                                };
+                            }
+                        } else if !chunks.is_empty() {
+                            let _ = output.output(time, utf8::join_lossy(chunks).as_bytes());
+                        }
+
+                        last_time = time;
                     }
 
                     Input(time, data) => {
-                        let _ = output.input(time, &data);
+                        let chunks = input_decoder.feed(&data);
+
+                        if !chunks.is_empty() {
+                            let _ = output.input(time, utf8::join_lossy(chunks).as_bytes());
+                        }
                     }
 
                     Resize(time, new_tty_size) => {
@@ -114,12 +169,19 @@ impl pty::Recorder for Recorder {
                         let _ = output.marker(time);
                     }
 
+                    Exit(time, status) => {
+                        flush_decoder(&mut output, &mut output_decoder, last_time, lossless);
+                        let _ = output.exit(time, status);
+                    }
+
                     Notification(text) => {
                         let _ = notifier.notify(text);
                     }
                 }
             }
 
+            // for sessions that ended without an exit status
+            flush_decoder(&mut output, &mut output_decoder, last_time, lossless);
             let _ = output.finish();
         });
 
@@ -194,7 +256,7 @@ impl Drop for JoinHandle {
     }
 }
 
-pub struct KeyBindings {
+pub(crate) struct KeyBindings {
     pub prefix: Key,
     pub pause: Key,
     pub add_marker: Key,
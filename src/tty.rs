@@ -21,11 +21,11 @@ impl From<TtySize> for (u16, u16) {
     }
 }
 
-pub trait Tty: io::Write + io::Read + AsFd {
+pub(crate) trait Tty: io::Write + io::Read + AsFd {
     fn get_size(&self) -> pty::Winsize;
 }
 
-pub struct DevTty {
+pub(crate) struct DevTty {
     file: RawTerminal<fs::File>,
 }
 
@@ -80,7 +80,7 @@ impl AsFd for DevTty {
     }
 }
 
-pub struct NullTty {
+pub(crate) struct NullTty {
     tx: OwnedFd,
     _rx: OwnedFd,
 }
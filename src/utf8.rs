@@ -0,0 +1,119 @@
+/// Splits a byte stream into valid UTF-8 text and invalid byte runs. An
+/// incomplete multibyte sequence at the end of a chunk is carried over to the
+/// next one instead of being reported as invalid. Used by the recorder and
+/// by readers of formats storing raw terminal output (script, ttyrec).
+#[derive(Default)]
+pub struct Utf8Decoder {
+    pending: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Chunk {
+    Text(String),
+    Invalid(Vec<u8>),
+}
+
+impl Utf8Decoder {
+    pub fn feed(&mut self, data: &[u8]) -> Vec<Chunk> {
+        let mut buf = std::mem::take(&mut self.pending);
+        buf.extend_from_slice(data);
+        let mut chunks = Vec::new();
+        let mut rest = &buf[..];
+
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(text) => {
+                    if !text.is_empty() {
+                        chunks.push(Chunk::Text(text.to_owned()));
+                    }
+
+                    break;
+                }
+
+                Err(e) => {
+                    let (valid, invalid) = rest.split_at(e.valid_up_to());
+
+                    if !valid.is_empty() {
+                        let text = std::str::from_utf8(valid).unwrap();
+                        chunks.push(Chunk::Text(text.to_owned()));
+                    }
+
+                    match e.error_len() {
+                        Some(len) => {
+                            match chunks.last_mut() {
+                                Some(Chunk::Invalid(bytes)) => {
+                                    bytes.extend_from_slice(&invalid[..len]);
+                                }
+
+                                _ => chunks.push(Chunk::Invalid(invalid[..len].to_vec())),
+                            }
+
+                            rest = &invalid[len..];
+                        }
+
+                        None => {
+                            self.pending = invalid.to_vec();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        chunks
+    }
+
+    pub fn finish(&mut self) -> Option<Vec<u8>> {
+        if self.pending.is_empty() {
+            None
+        } else {
+            Some(std::mem::take(&mut self.pending))
+        }
+    }
+}
+
+pub fn join_lossy(chunks: Vec<Chunk>) -> String {
+    chunks
+        .into_iter()
+        .map(|chunk| match chunk {
+            Chunk::Text(text) => text,
+            Chunk::Invalid(bytes) => String::from_utf8_lossy(&bytes).to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Chunk, Utf8Decoder};
+
+    #[test]
+    fn split_multibyte_char() {
+        let mut decoder = Utf8Decoder::default();
+        let data = "zażółć".as_bytes();
+
+        assert_eq!(decoder.feed(&data[..3]), vec![Chunk::Text("za".to_owned())]);
+        assert_eq!(decoder.feed(&data[3..4]), vec![Chunk::Text("ż".to_owned())]);
+        assert_eq!(decoder.feed(&data[4..5]), vec![]);
+        assert_eq!(
+            decoder.feed(&data[5..]),
+            vec![Chunk::Text("ółć".to_owned())]
+        );
+        assert_eq!(decoder.finish(), None);
+    }
+
+    #[test]
+    fn invalid_bytes() {
+        let mut decoder = Utf8Decoder::default();
+
+        assert_eq!(
+            decoder.feed(b"ab\xff\xfecd\xe2\x82"),
+            vec![
+                Chunk::Text("ab".to_owned()),
+                Chunk::Invalid(vec![0xff, 0xfe]),
+                Chunk::Text("cd".to_owned()),
+            ]
+        );
+
+        assert_eq!(decoder.finish(), Some(vec![0xe2, 0x82]));
+    }
+}
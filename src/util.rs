@@ -15,6 +15,38 @@ pub fn get_local_path(filename: &str) -> Result<Box<dyn AsRef<Path>>> {
     }
 }
 
+/// Terminal size given as `COLSxROWS`, where either number can be omitted
+/// (e.g. `100x`) to keep that dimension unchanged.
+#[derive(Clone, Copy, Debug)]
+pub struct TtySize(pub (Option<u16>, Option<u16>));
+
+pub fn parse_tty_size(s: &str) -> Result<TtySize> {
+    match s.split_once('x') {
+        Some((cols, "")) => {
+            let cols: u16 = cols.parse()?;
+
+            Ok(TtySize((Some(cols), None)))
+        }
+
+        Some(("", rows)) => {
+            let rows: u16 = rows.parse()?;
+
+            Ok(TtySize((None, Some(rows))))
+        }
+
+        Some((cols, rows)) => {
+            let cols: u16 = cols.parse()?;
+            let rows: u16 = rows.parse()?;
+
+            Ok(TtySize((Some(cols), Some(rows))))
+        }
+
+        None => {
+            bail!("expected COLSxROWS")
+        }
+    }
+}
+
 const LINK_REL_SELECTOR: &str = r#"link[rel="alternate"][type="application/x-asciicast"], link[rel="alternate"][type="application/asciicast+json"]"#;
 
 fn download_asciicast(url: &str) -> Result<NamedTempFile> {